@@ -0,0 +1,337 @@
+//! Encodings of pseudo-boolean constraints into CNF.
+//!
+//! Every auxiliary variable is defined by both directions of its gate, so it is a function of the
+//! original variables. The CNF therefore has the same model count as the formula, projected on the
+//! `c p show` variables as well as over all variables.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Zero};
+use crate::parsing::equation_ds::{EquationPart, File};
+use crate::parsing::preprocessing::preprocess_file;
+
+/// Encoding used for constraints that are not already clauses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CnfEncoding {
+    /// Sequential weight counter with one register per literal and partial sum up to the degree.
+    SequentialCounter,
+    /// Generalized totalizer, a balanced tree of unary sums capped at the degree.
+    #[default]
+    Totalizer,
+    /// Binary decision diagram over the literals ordered by decreasing coefficient.
+    Bdd,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Signal {
+    True,
+    False,
+    Lit(i32),
+}
+
+impl Signal {
+    fn not(self) -> Signal {
+        match self {
+            Signal::True => Signal::False,
+            Signal::False => Signal::True,
+            Signal::Lit(l) => Signal::Lit(-l),
+        }
+    }
+}
+
+/// `sum of weight * literal >= degree` with positive weights and DIMACS literals.
+struct Constraint {
+    literals: Vec<(i64, i32)>,
+    degree: i64,
+}
+
+struct Cnf {
+    variables: u32,
+    clauses: Vec<Vec<i32>>,
+}
+
+impl Cnf {
+    fn fresh(&mut self) -> Signal {
+        self.variables += 1;
+        Signal::Lit(self.variables as i32)
+    }
+
+    /// Adds the disjunction of the signals, true signals drop the clause and false signals are left out.
+    fn clause(&mut self, signals: &[Signal]) {
+        let mut clause = Vec::new();
+        for s in signals {
+            match s {
+                Signal::True => return,
+                Signal::False => {}
+                Signal::Lit(l) => clause.push(*l),
+            }
+        }
+        self.clauses.push(clause);
+    }
+
+    fn and(&mut self, a: Signal, b: Signal) -> Signal {
+        match (a, b) {
+            (Signal::False, _) | (_, Signal::False) => Signal::False,
+            (Signal::True, s) | (s, Signal::True) => s,
+            _ if a == b => a,
+            _ => {
+                let v = self.fresh();
+                self.clause(&[v.not(), a]);
+                self.clause(&[v.not(), b]);
+                self.clause(&[v, a.not(), b.not()]);
+                v
+            }
+        }
+    }
+
+    fn or(&mut self, signals: &[Signal]) -> Signal {
+        let mut inputs: Vec<Signal> = Vec::new();
+        for s in signals {
+            match s {
+                Signal::True => return Signal::True,
+                Signal::False => {}
+                s => {
+                    if !inputs.contains(s) {
+                        inputs.push(*s);
+                    }
+                }
+            }
+        }
+        match inputs.len() {
+            0 => Signal::False,
+            1 => inputs[0],
+            _ => {
+                let v = self.fresh();
+                let mut clause = vec![v.not()];
+                clause.extend(inputs.iter().copied());
+                self.clause(&clause);
+                for s in inputs {
+                    self.clause(&[v, s.not()]);
+                }
+                v
+            }
+        }
+    }
+
+    /// If `x` then `a` else `b`.
+    fn ite(&mut self, x: Signal, a: Signal, b: Signal) -> Signal {
+        match (a, b) {
+            _ if a == b => a,
+            (Signal::True, Signal::False) => x,
+            (Signal::False, Signal::True) => x.not(),
+            _ => {
+                let v = self.fresh();
+                self.clause(&[x.not(), a.not(), v]);
+                self.clause(&[x.not(), a, v.not()]);
+                self.clause(&[x, b.not(), v]);
+                self.clause(&[x, b, v.not()]);
+                v
+            }
+        }
+    }
+
+    fn encode(&mut self, mut constraint: Constraint, encoding: CnfEncoding) {
+        let degree = constraint.degree;
+        if degree <= 0 {
+            return;
+        }
+        for (w, _) in &mut constraint.literals {
+            *w = (*w).min(degree);
+        }
+        if constraint.literals.iter().map(|(w, _)| w).sum::<i64>() < degree {
+            self.clause(&[]);
+            return;
+        }
+        if constraint.literals.iter().all(|(w, _)| *w == degree) {
+            let clause: Vec<Signal> = constraint.literals.iter().map(|(_, l)| Signal::Lit(*l)).collect();
+            self.clause(&clause);
+            return;
+        }
+        let output = match encoding {
+            CnfEncoding::SequentialCounter => self.sequential_counter(&constraint.literals, degree),
+            CnfEncoding::Totalizer => {
+                let sums = self.totalizer(&constraint.literals, degree);
+                sums[&degree]
+            }
+            CnfEncoding::Bdd => {
+                constraint.literals.sort_by_key(|l| std::cmp::Reverse(l.0));
+                let mut suffix = vec![0; constraint.literals.len() + 1];
+                for i in (0..constraint.literals.len()).rev() {
+                    suffix[i] = suffix[i + 1] + constraint.literals[i].0;
+                }
+                self.bdd(&constraint.literals, &suffix, 0, degree, &mut HashMap::new())
+            }
+        };
+        self.clause(&[output]);
+    }
+
+    /// Register `j` holds if the literals seen so far sum up to at least `j`.
+    fn sequential_counter(&mut self, literals: &[(i64, i32)], degree: i64) -> Signal {
+        let mut registers = vec![Signal::False; degree as usize + 1];
+        registers[0] = Signal::True;
+        for (w, l) in literals {
+            let mut next = registers.clone();
+            for j in 1..=degree {
+                let rest = if j - w <= 0 { Signal::True } else { registers[(j - w) as usize] };
+                let take = self.and(Signal::Lit(*l), rest);
+                next[j as usize] = self.or(&[registers[j as usize], take]);
+            }
+            registers = next;
+        }
+        registers[degree as usize]
+    }
+
+    /// Maps every reachable sum of the literals, capped at the degree, to a signal that holds if the sum is reached.
+    fn totalizer(&mut self, literals: &[(i64, i32)], degree: i64) -> BTreeMap<i64, Signal> {
+        if literals.len() == 1 {
+            return BTreeMap::from([(literals[0].0, Signal::Lit(literals[0].1))]);
+        }
+        let (left, right) = literals.split_at(literals.len() / 2);
+        let mut left = self.totalizer(left, degree);
+        let mut right = self.totalizer(right, degree);
+        left.insert(0, Signal::True);
+        right.insert(0, Signal::True);
+
+        let mut terms: BTreeMap<i64, Vec<Signal>> = BTreeMap::new();
+        for (a, sa) in &left {
+            for (b, sb) in &right {
+                if a + b == 0 {
+                    continue;
+                }
+                let term = self.and(*sa, *sb);
+                terms.entry((a + b).min(degree)).or_default().push(term);
+            }
+        }
+        // a sum is reached if it or a larger sum is reached exactly by one of the pairs
+        let mut sums = BTreeMap::new();
+        let mut larger = Signal::False;
+        for (value, mut inputs) in terms.into_iter().rev() {
+            inputs.push(larger);
+            larger = self.or(&inputs);
+            sums.insert(value, larger);
+        }
+        sums
+    }
+
+    fn bdd(&mut self, literals: &[(i64, i32)], suffix: &[i64], i: usize, degree: i64, memo: &mut HashMap<(usize, i64), Signal>) -> Signal {
+        if degree <= 0 {
+            return Signal::True;
+        }
+        if suffix[i] < degree {
+            return Signal::False;
+        }
+        if let Some(s) = memo.get(&(i, degree)) {
+            return *s;
+        }
+        let (w, l) = literals[i];
+        let high = self.bdd(literals, suffix, i + 1, degree - w, memo);
+        let low = self.bdd(literals, suffix, i + 1, degree, memo);
+        let node = self.ite(Signal::Lit(l), high, low);
+        memo.insert((i, degree), node);
+        node
+    }
+}
+
+/// Preprocesses a copy of the file and writes it as DIMACS CNF with a `c p show` line for the
+/// projection of the file, or all of its variables, and `c p weight` lines for its literal weights.
+/// Weights without a finite decimal representation are written as exact fractions `p/q`, which the
+/// DIMACS reader of this crate accepts but other model counters may not.
+pub fn encode_file(file: &File, encoding: CnfEncoding) -> String {
+    let show: Vec<u32> = match &file.projection {
+        Some(projection) => projection.clone(),
+        None => (0..file.variable_count()).collect(),
+    };
+    let preprocessed = preprocess_file(file.clone());
+    let mut cnf = Cnf {
+        variables: preprocessed.variable_count(),
+        clauses: Vec::new(),
+    };
+
+    for equation in &preprocessed.equations {
+        let mut constraint = Constraint {
+            literals: Vec::new(),
+            degree: 0,
+        };
+        if let Some(EquationPart::Factor(f)) = equation.e2.literals.first() {
+            constraint.degree = *f;
+        }
+        for part in &equation.e1.literals {
+            match part {
+                // coefficients are positive after preprocessing
+                EquationPart::Literal { factor, name, negated } => {
+                    let literal = if *negated { -(*name as i32 + 1) } else { *name as i32 + 1 };
+                    constraint.literals.push((*factor, literal));
+                }
+                EquationPart::Factor(_) => {}
+                EquationPart::Product { .. } => unreachable!("products are linearized during preprocessing"),
+            }
+        }
+        cnf.encode(constraint, encoding);
+    }
+    for substitution in &preprocessed.substitutions {
+        let name = substitution.name as i32 + 1;
+        let literal = if substitution.negated { -(substitution.literal as i32 + 1) } else { substitution.literal as i32 + 1 };
+        cnf.clauses.push(vec![-name, literal]);
+        cnf.clauses.push(vec![name, -literal]);
+    }
+
+    let mut result = String::new();
+    writeln!(result, "p cnf {} {}", cnf.variables, cnf.clauses.len()).unwrap();
+    result.push_str("c p show");
+    for v in &show {
+        write!(result, " {}", v + 1).unwrap();
+    }
+    result.push_str(" 0\n");
+    for w in &file.weights {
+        let literal = if w.negated { -(w.name as i64 + 1) } else { w.name as i64 + 1 };
+        let weight = decimal_string(&w.weight).unwrap_or_else(|| w.weight.to_string());
+        writeln!(result, "c p weight {} {} 0", literal, weight).unwrap();
+    }
+    for clause in &cnf.clauses {
+        for l in clause {
+            write!(result, "{} ", l).unwrap();
+        }
+        result.push_str("0\n");
+    }
+    result
+}
+
+/// Writes the weight as exact decimal number, `None` if its denominator has other prime factors than 2 and 5.
+fn decimal_string(weight: &BigRational) -> Option<String> {
+    let mut denominator = weight.denom().clone();
+    let mut twos = 0;
+    let mut fives = 0;
+    let two = BigInt::from(2);
+    let five = BigInt::from(5);
+    while (&denominator % &two).is_zero() {
+        denominator /= &two;
+        twos += 1;
+    }
+    while (&denominator % &five).is_zero() {
+        denominator /= &five;
+        fives += 1;
+    }
+    let digits: u32 = twos.max(fives);
+    if !denominator.is_one() {
+        return None;
+    }
+    let scaled = weight * BigRational::from_integer(BigInt::from(10).pow(digits));
+    let mut value = scaled.to_integer().to_string();
+    if digits == 0 {
+        return Some(value);
+    }
+    let negative = value.starts_with('-');
+    if negative {
+        value.remove(0);
+    }
+    while value.len() <= digits as usize {
+        value.insert(0, '0');
+    }
+    value.insert(value.len() - digits as usize, '.');
+    if negative {
+        value.insert(0, '-');
+    }
+    Some(value)
+}
@@ -3,18 +3,38 @@ use rand::Rng;
 use crate::parsing::equation_ds::{Equation, EquationPart, EquationSide, EquationSymbol, File};
 use crate::parsing::parser::parse;
 
-pub fn generate_pb_formula(number_equations: u32, number_variables: u32, size_equation: u32, max_factor: i32) -> File {
+/// Generates `number_equations` random constraints `sum >= 1` over `size_equation` literals with
+/// coefficients between 1 and `max_factor`. Fails if there are no constraints or no variables, the
+/// constraints are empty or `max_factor` is below 1.
+pub fn generate_pb_formula(number_equations: u32, number_variables: u32, size_equation: u32, max_factor: i32) -> Result<File, String> {
+    if number_equations == 0 {
+        return Err("at least one constraint is needed".to_string());
+    }
+    if number_variables == 0 {
+        return Err("at least one variable is needed".to_string());
+    }
+    if size_equation == 0 {
+        return Err("constraints need at least one literal".to_string());
+    }
+    if max_factor < 1 {
+        return Err(format!("the largest coefficient {} is below 1", max_factor));
+    }
     let mut file = File{
         equations: Vec::new(),
         name_map: BiHashMap::new(),
-        name_index: 0
+        name_index: 0,
+        declared_variables: None,
+        objective: None,
+        weights: Vec::new(),
+        projection: None,
+        substitutions: Vec::new()
     };
 
-    for i in (1..=number_equations) {
+    for _ in 1..=number_equations {
         file.equations.push(generate_random_equation(number_variables, size_equation, max_factor, &mut file.name_map));
     }
 
-    parse(&file.to_string())
+    parse(&file.to_string()).map_err(|e| e.to_string())
 }
 
 fn generate_random_equation(number_variables: u32, size_equation: u32, max_factor: i32, name_map: &mut BiMap<String, u32>) -> Equation {
@@ -25,8 +45,7 @@ fn generate_random_equation(number_variables: u32, size_equation: u32, max_facto
         literals: Vec::new()
     };
     random_equation_side_2.literals.push(EquationPart::Factor(1));
-    //random_equation_side_2.literals.push(generate_random_rhs(size_equation as i32, max_factor));
-    for i in (1..=size_equation){
+    for _ in 1..=size_equation {
         random_equation_side_1.literals.push(generate_random_literal(number_variables, max_factor, name_map));
     }
 
@@ -37,27 +56,14 @@ fn generate_random_equation(number_variables: u32, size_equation: u32, max_facto
     }
 }
 
-fn generate_random_rhs(size_equation: i32, max_factor: i32) -> EquationPart {
-    let random_number = rand::thread_rng().gen_range(0..=size_equation/2*max_factor);
-    EquationPart::Factor(random_number as i32)
-}
-
-fn generate_random_equation_symbol() -> EquationSymbol {
-    let random_number = rand::thread_rng().gen_range(0..=1);
-    if random_number == 0 {
-        EquationSymbol::eq
-    }else{
-        EquationSymbol::ge
-    }
-}
-
 fn generate_random_literal(number_variables: u32, max_factor: i32, name_map: &mut BiMap<String, u32>) -> EquationPart {
     let random_variable_index = rand::thread_rng().gen_range(1..=number_variables);
-    let random_factor = 1; // rand::thread_rng().gen_range(1..=max_factor);
+    let random_factor = rand::thread_rng().gen_range(1..=max_factor);
     let name = format!("x_{}", random_variable_index);
     name_map.insert(name, random_variable_index);
     EquationPart::Literal {
-        factor: random_factor,
-        name: random_variable_index
+        factor: random_factor.into(),
+        name: random_variable_index,
+        negated: false
     }
 }
\ No newline at end of file
@@ -0,0 +1,256 @@
+//! Model counting for pseudo-boolean formulas.
+//!
+//! The modules expose every stage of the pipeline, the functions in this file
+//! wrap them into the usual parse, preprocess, build and count sequence.
+
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::Path;
+pub use num_bigint::BigUint;
+pub use num_rational::BigRational;
+use crate::parsing::equation_ds::File;
+use crate::parsing::parser::ParseError;
+use crate::parsing::preprocessing::PreprocessingReport;
+use crate::solving::pb_ds::PBFormula;
+use crate::solving::cache::CacheStats;
+use crate::solving::ddnnf::{Compiler, Ddnnf};
+use crate::solving::heuristics::{BestPartition, Heuristic, Occurrence, Vsads, WeightedOccurrence};
+use crate::solving::ordering::{EliminationHeuristic, VariableOrder};
+use crate::solving::parallel::ParallelSolver;
+use crate::solving::solver::{weigh_implied, SearchStats, Solver};
+use crate::solving::weights::{Unweighted, Weights};
+
+pub mod parsing {
+    pub mod parser;
+    pub mod dimacs;
+    pub mod equation_ds;
+    pub mod preprocessing;
+}
+pub mod solving {
+    pub mod cache;
+    pub mod ddnnf;
+    pub mod enumeration;
+    pub mod heuristics;
+    pub mod ordering;
+    pub mod parallel;
+    pub mod pb_ds;
+    pub mod sampling;
+    pub mod solver;
+    pub mod weights;
+}
+
+pub mod encoding {
+    pub mod cnf;
+}
+
+pub mod generating {
+    pub mod generator;
+}
+
+/// Search procedure used to count a formula.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Engine {
+    /// DPLL search with a cache of already counted subformulas.
+    Plain,
+    /// DPLL search that additionally splits the formula into disconnected components.
+    Components,
+}
+
+/// Decision heuristic of the search, see [`solving::heuristics`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Branching {
+    /// Most frequent variable of every subformula.
+    Occurrence,
+    /// Most frequent variable, weighted by its coefficients relative to the degrees of its clauses.
+    WeightedOccurrence,
+    /// Activity in recent conflicts combined with the number of occurrences.
+    Vsads,
+    /// Variable whose assignment leaves the smallest largest component.
+    BestPartition,
+    /// Static order computed once from a tree decomposition of the primal graph.
+    Static(EliminationHeuristic),
+}
+
+/// Options for [`count`].
+#[derive(Clone, Debug)]
+pub struct CountOptions {
+    /// Search procedure, see [`Engine`]. [`Engine::Components`] by default.
+    pub engine: Engine,
+    /// Memory budget of the cache of counted subformulas in bytes, least recently used entries are evicted beyond it.
+    pub cache_memory: usize,
+    /// Range of the progress output written to stderr, `None` disables it. Only the sequential search reports progress.
+    pub progress: Option<(u32, u32)>,
+    /// Variables the count is projected on, `None` counts the assignments to all variables.
+    pub projection: Option<Vec<u32>>,
+    /// Number of threads, more than one counts branches and components in parallel with the same result.
+    pub threads: usize,
+    /// Decision heuristic of the search, see [`Branching`]. [`Branching::Occurrence`] by default.
+    pub branching: Branching,
+}
+
+impl Default for CountOptions {
+    fn default() -> Self {
+        CountOptions {
+            engine: Engine::Components,
+            cache_memory: 1 << 30,
+            progress: None,
+            projection: None,
+            threads: 1,
+            branching: Branching::Occurrence,
+        }
+    }
+}
+
+/// Statistics of a run of [`count_weighted_with_stats`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CountStats {
+    pub cache: CacheStats,
+    /// Conflict analysis of the search, both engines learn constraints.
+    pub search: SearchStats,
+}
+
+/// Error of the functions reading a formula from disk.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Parse(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+/// Parses a formula given as a string, either OPB or DIMACS CNF recognized by its `p cnf` header.
+/// Every coefficient and degree has to fit in `i32`, their sums are not limited.
+pub fn parse_str(content: &str) -> Result<File, ParseError> {
+    parsing::parser::parse(content)
+}
+
+/// Reads and parses the formula stored at `path`.
+pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<File, Error> {
+    let content = fs::read_to_string(path)?;
+    Ok(parse_str(&content)?)
+}
+
+/// Normalizes all equations to the form `sum of literals >= constant`.
+pub fn preprocess(file: File) -> File {
+    parsing::preprocessing::preprocess_file(file)
+}
+
+/// Like [`preprocess`], additionally reports the constraints the preprocessing passes removed.
+pub fn preprocess_with_report(file: File) -> (File, PreprocessingReport) {
+    parsing::preprocessing::preprocess_file_with_report(file)
+}
+
+/// Builds the solver representation of a preprocessed file.
+pub fn build_formula(file: &File) -> PBFormula {
+    solving::pb_ds::new(file)
+}
+
+/// Counts the satisfying assignments of `formula`.
+pub fn count(formula: PBFormula, options: &CountOptions) -> BigUint {
+    count_weighted(formula, Unweighted, options)
+}
+
+/// Sums the weights of the satisfying assignments of `formula`, the weight of an assignment
+/// is the product of the weights of its literals.
+pub fn count_weighted<W>(formula: PBFormula, weights: W, options: &CountOptions) -> W::Value
+where
+    W: Weights + Sync,
+    W::Value: Send,
+{
+    count_weighted_with_stats(formula, weights, options).0
+}
+
+/// Like [`count_weighted`], additionally returns the statistics of the cache and the search at the end of the run.
+pub fn count_weighted_with_stats<W>(formula: PBFormula, weights: W, options: &CountOptions) -> (W::Value, CountStats)
+where
+    W: Weights + Sync,
+    W::Value: Send,
+{
+    let projection: Option<HashSet<u32>> = options.projection.as_ref().map(|p| p.iter().copied().collect());
+    // literals forced before the first decision
+    let (formula, implied) = formula.propagate();
+    let heuristic: Box<dyn Heuristic + Send + Sync> = match options.branching {
+        Branching::Occurrence => Box::new(Occurrence),
+        Branching::WeightedOccurrence => Box::new(WeightedOccurrence),
+        Branching::Vsads => Box::new(Vsads::default()),
+        Branching::BestPartition => Box::new(BestPartition),
+        Branching::Static(elimination) => Box::new(VariableOrder::new(&formula, elimination)),
+    };
+    if options.threads > 1 {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(options.threads)
+            .build()
+            .expect("cannot create the thread pool");
+        let mut solver = ParallelSolver::new(weights, options.cache_memory, options.threads);
+        solver.projection = projection;
+        solver.heuristic = heuristic.into();
+        let res = pool.install(|| match options.engine {
+            Engine::Plain => solver.count(&formula),
+            Engine::Components => solver.count_disconnected_components(&formula),
+        });
+        let res = weigh_implied(&solver.weights, solver.projection.as_ref(), &implied, res);
+        let stats = CountStats {
+            cache: solver.cache_stats(),
+            search: solver.stats(),
+        };
+        return (res, stats);
+    }
+
+    let mut solver = Solver::new(weights, options.cache_memory);
+    solver.projection = projection;
+    solver.heuristic = heuristic;
+    let progress = options.progress.map(|(start, end)| (start, end.max(start)));
+    let res = match options.engine {
+        Engine::Plain => solver.count(&formula, progress),
+        Engine::Components => solver.count_disconnected_components(formula, progress),
+    };
+    let res = weigh_implied(&solver.weights, solver.projection.as_ref(), &implied, res);
+    let stats = CountStats {
+        cache: solver.cache.stats(),
+        search: solver.stats,
+    };
+    (res, stats)
+}
+
+/// Parses, preprocesses and counts the formula stored at `path`.
+pub fn count_file<P: AsRef<Path>>(path: P, options: &CountOptions) -> Result<BigUint, Error> {
+    let file = parse_file(path)?;
+    Ok(count(build_formula(&preprocess(file)), options))
+}
+
+/// Compiles `formula` into a d-DNNF by recording the search of [`count_weighted`], `file` is the
+/// preprocessed file the formula was built from.
+pub fn compile(formula: PBFormula, file: &File, options: &CountOptions) -> Ddnnf {
+    let nnf = count_weighted(formula, Compiler, options);
+    Ddnnf::new(&nnf, file.variable_count(), &file.substitutions)
+}
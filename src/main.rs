@@ -1,118 +1,710 @@
-use std::collections::HashMap;
-use std::fmt::{Write, Display, Formatter};
+use std::collections::HashSet;
 use std::fs;
-use pest::Parser;
-use crate::parsing::equation_ds::File;
-use crate::solving::solver::count_disconnected_components;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Instant;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use num_traits::{ToPrimitive, Zero};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use parser_test::{build_formula, BigRational, compile, count_weighted_with_stats, parse_file, preprocess, preprocess_with_report, Branching, CountOptions, Error};
+use parser_test::generating::generator::generate_pb_formula;
+use parser_test::parsing::equation_ds::{EquationPart, EquationSymbol, File};
+use parser_test::parsing::parser::ParseError;
+use parser_test::solving::ordering::{EliminationHeuristic, VariableOrder};
+use parser_test::solving::solver::formula_to_disconnected_formula;
+use parser_test::solving::ddnnf::Ddnnf;
+use parser_test::solving::enumeration::Enumerator;
+use parser_test::solving::sampling::Sampler;
+use parser_test::solving::weights::{LiteralWeights, Unweighted, Weights};
 
-mod parsing {
-    pub mod parser;
-    pub mod equation_ds;
-    pub mod preprocessing;
+const EXIT_USAGE_ERROR: u8 = 2;
+const EXIT_IO_ERROR: u8 = 3;
+const EXIT_PARSE_ERROR: u8 = 4;
+
+#[derive(Parser)]
+#[command(version, about = "Model counter for pseudo-boolean formulas")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
 }
-mod solving {
-    pub mod pb_ds;
-    pub mod solver;
+
+#[derive(Subcommand)]
+enum Command {
+    /// Count the satisfying assignments of a formula
+    Count(CountArgs),
+    /// Compile a formula into a d-DNNF in the c2d/d4 `.nnf` format
+    Compile(CompileArgs),
+    /// Count a formula under assumptions and the models of every literal using its d-DNNF
+    Query(QueryArgs),
+    /// Draw uniformly distributed satisfying assignments of a formula
+    Sample(SampleArgs),
+    /// List the satisfying assignments of a formula
+    Enumerate(EnumerateArgs),
+    /// Convert a formula into another output format
+    Convert(ConvertArgs),
+    /// Generate a random formula
+    Generate(GenerateArgs),
+    /// Print statistics about a formula
+    Stats(StatsArgs),
 }
 
-mod generating {
-    pub mod generator;
+#[derive(Args)]
+struct CountArgs {
+    /// Input file, OPB or DIMACS CNF
+    input: PathBuf,
+    /// Counting engine
+    #[arg(long, value_enum, default_value_t = Engine::Components)]
+    engine: Engine,
+    /// Memory budget of the component cache in MiB
+    #[arg(long, default_value_t = 1024)]
+    cache_memory: usize,
+    /// Value the progress output starts at
+    #[arg(long, default_value_t = 0)]
+    progress_start: u32,
+    /// Value the progress output ends at, equal to the start disables progress output
+    #[arg(long, default_value_t = 100)]
+    progress_end: u32,
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+    /// Compute the weighted model count using the `* weight` (`c p weight` in DIMACS) annotations of the input
+    #[arg(long)]
+    weighted: bool,
+    /// Count the distinct assignments to the variables declared with `* show` (`c p show` in DIMACS)
+    #[arg(long)]
+    projected: bool,
+    /// Number of threads, more than one disables the progress output
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+    /// Decision heuristic of the search
+    #[arg(long, value_enum, default_value_t = Heuristic::Occurrence)]
+    heuristic: Heuristic,
 }
 
-fn main() {
+#[derive(Args)]
+struct CompileArgs {
+    /// Input file, OPB or DIMACS CNF
+    input: PathBuf,
+    /// Counting engine whose search is recorded
+    #[arg(long, value_enum, default_value_t = Engine::Components)]
+    engine: Engine,
+    /// Memory budget of the component cache in MiB
+    #[arg(long, default_value_t = 1024)]
+    cache_memory: usize,
+    /// Compile the projection on the variables declared with `* show` (`c p show` in DIMACS)
+    #[arg(long)]
+    projected: bool,
+    /// Decision heuristic of the search
+    #[arg(long, value_enum, default_value_t = Heuristic::Occurrence)]
+    heuristic: Heuristic,
+    /// Output file, stdout if omitted
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
 
+#[derive(Args)]
+struct QueryArgs {
+    /// Input file, OPB or DIMACS CNF
+    input: PathBuf,
+    /// Comma separated literals every counted model contains, `~x` is the negation of `x`
+    #[arg(long, value_delimiter = ',')]
+    assume: Vec<String>,
+    /// Use the `* weight` (`c p weight` in DIMACS) annotations of the input
+    #[arg(long)]
+    weighted: bool,
+    /// Count the distinct assignments to the variables declared with `* show` (`c p show` in DIMACS)
+    #[arg(long)]
+    projected: bool,
+    /// Counting engine whose search is compiled
+    #[arg(long, value_enum, default_value_t = Engine::Components)]
+    engine: Engine,
+    /// Memory budget of the component cache in MiB
+    #[arg(long, default_value_t = 1024)]
+    cache_memory: usize,
+    /// Decision heuristic of the search
+    #[arg(long, value_enum, default_value_t = Heuristic::Occurrence)]
+    heuristic: Heuristic,
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
 
-    //let random_formula = generating::generator::generate_pb_formula(50, 100, 5, 1);
-    //println!("{}\n", random_formula.to_pbcount_string());
-    //count_with_disconnected_components_on_string(random_formula.to_pbcount_string());
-    //run_solver_on_path("/home/stefan/stefan-vill-master/tmp_eval/tmp4.opb");
-    //run_solver_on_file(random_formula);
+#[derive(Args)]
+struct SampleArgs {
+    /// Input file, OPB or DIMACS CNF
+    input: PathBuf,
+    /// Number of samples
+    #[arg(short = 'k', long, default_value_t = 1)]
+    samples: usize,
+    /// Seed of the random number generator, a random seed if omitted
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Sample the assignments to the variables declared with `* show` (`c p show` in DIMACS)
+    #[arg(long)]
+    projected: bool,
+    /// Counting engine whose search is compiled
+    #[arg(long, value_enum, default_value_t = Engine::Components)]
+    engine: Engine,
+    /// Memory budget of the component cache in MiB
+    #[arg(long, default_value_t = 1024)]
+    cache_memory: usize,
+    /// Decision heuristic of the search
+    #[arg(long, value_enum, default_value_t = Heuristic::Occurrence)]
+    heuristic: Heuristic,
+    /// Output format, one line of literals per sample or a JSON array of assignments
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
 
+#[derive(Args)]
+struct EnumerateArgs {
+    /// Input file, OPB or DIMACS CNF
+    input: PathBuf,
+    /// Largest number of listed assignments
+    #[arg(long)]
+    limit: Option<u64>,
+    /// List the distinct assignments to the variables declared with `* show` (`c p show` in DIMACS)
+    #[arg(long)]
+    projected: bool,
+    /// Show variables that can take both values as `*x` instead of listing both assignments
+    #[arg(long)]
+    dont_cares: bool,
+    /// Output format, one line of literals or one JSON object per assignment
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
 
+#[derive(Args)]
+struct ConvertArgs {
+    /// Input file
+    input: PathBuf,
+    /// Target format
+    #[arg(long, value_enum, default_value_t = ConvertFormat::Pbcount)]
+    to: ConvertFormat,
+    /// Preprocess the formula before writing it
+    #[arg(long)]
+    preprocess: bool,
+    /// Encoding of pseudo-boolean constraints in DIMACS output
+    #[arg(long, value_enum, default_value_t = CnfEncoding::Totalizer)]
+    encoding: CnfEncoding,
+    /// Output file, stdout if omitted
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct GenerateArgs {
+    /// Number of equations
+    #[arg(long, default_value_t = 50)]
+    equations: u32,
+    /// Number of variables
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+    variables: u32,
+    /// Number of literals per equation
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    size: u32,
+    /// Largest coefficient, the coefficients are drawn uniformly from 1 to it
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(i32).range(1..))]
+    max_factor: i32,
+    /// Format of the generated formula
+    #[arg(long, value_enum, default_value_t = ConvertFormat::Opb)]
+    to: ConvertFormat,
+    /// Encoding of pseudo-boolean constraints in DIMACS output
+    #[arg(long, value_enum, default_value_t = CnfEncoding::Totalizer)]
+    encoding: CnfEncoding,
+    /// Output file, stdout if omitted
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct StatsArgs {
+    /// Input file
+    input: PathBuf,
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+}
 
-    //let unparsed_file = fs::read_to_string("/home/stefan/stefan-vill-master/tmp_eval/tmp5.opb").expect("cannot read file");
-    //let file = parsing::parser::parse(&unparsed_file);
-    //println!("{}", file.to_dimacs_string());
+#[derive(Clone, Copy, ValueEnum)]
+enum Engine {
+    /// Plain DPLL search with caching
+    Plain,
+    /// DPLL search that splits the formula into disconnected components
+    Components,
+}
 
+impl From<Engine> for parser_test::Engine {
+    fn from(engine: Engine) -> Self {
+        match engine {
+            Engine::Plain => parser_test::Engine::Plain,
+            Engine::Components => parser_test::Engine::Components,
+        }
+    }
+}
 
+#[derive(Clone, Copy, ValueEnum)]
+enum Heuristic {
+    /// Most frequent variable of every subformula
+    Occurrence,
+    /// Occurrences weighted by the coefficients
+    WeightedOccurrence,
+    /// Conflict activity combined with occurrences
+    Vsads,
+    /// Variable leaving the smallest largest component, slow
+    BestPartition,
+    /// Static order from a min-fill tree decomposition
+    MinFill,
+    /// Static order from a min-degree tree decomposition
+    MinDegree,
+}
 
+impl From<Heuristic> for Branching {
+    fn from(heuristic: Heuristic) -> Self {
+        match heuristic {
+            Heuristic::Occurrence => Branching::Occurrence,
+            Heuristic::WeightedOccurrence => Branching::WeightedOccurrence,
+            Heuristic::Vsads => Branching::Vsads,
+            Heuristic::BestPartition => Branching::BestPartition,
+            Heuristic::MinFill => Branching::Static(EliminationHeuristic::MinFill),
+            Heuristic::MinDegree => Branching::Static(EliminationHeuristic::MinDegree),
+        }
+    }
+}
 
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
 
+#[derive(Clone, Copy, ValueEnum)]
+enum ConvertFormat {
+    /// Equations with the original variable names
+    Opb,
+    /// Input format of PBCount with numbered variables
+    Pbcount,
+    /// DIMACS CNF, weights without a finite decimal representation are written as fractions `p/q`
+    Dimacs,
+}
 
-    //print_file_as_pbcount_string(file);
+#[derive(Clone, Copy, ValueEnum)]
+enum CnfEncoding {
+    /// Sequential weight counter
+    SequentialCounter,
+    /// Generalized totalizer
+    Totalizer,
+    /// Binary decision diagram
+    Bdd,
+}
 
+impl From<CnfEncoding> for parser_test::encoding::cnf::CnfEncoding {
+    fn from(encoding: CnfEncoding) -> Self {
+        match encoding {
+            CnfEncoding::SequentialCounter => parser_test::encoding::cnf::CnfEncoding::SequentialCounter,
+            CnfEncoding::Totalizer => parser_test::encoding::cnf::CnfEncoding::Totalizer,
+            CnfEncoding::Bdd => parser_test::encoding::cnf::CnfEncoding::Bdd,
+        }
+    }
+}
 
+enum CliError {
+    Usage(String),
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, Box<ParseError>),
+}
 
+impl CliError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            CliError::Usage(..) => ExitCode::from(EXIT_USAGE_ERROR),
+            CliError::Io(..) => ExitCode::from(EXIT_IO_ERROR),
+            CliError::Parse(..) => ExitCode::from(EXIT_PARSE_ERROR),
+        }
+    }
+}
 
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}", message),
+            CliError::Io(path, e) => write!(f, "cannot access {}: {}", path.display(), e),
+            CliError::Parse(path, e) => write!(f, "cannot parse {}:{}", path.display(), e.annotated()),
+        }
+    }
+}
 
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Count(args) => run_count(args),
+        Command::Compile(args) => run_compile(args),
+        Command::Query(args) => run_query(args),
+        Command::Sample(args) => run_sample(args),
+        Command::Enumerate(args) => run_enumerate(args),
+        Command::Convert(args) => run_convert(args),
+        Command::Generate(args) => run_generate(args),
+        Command::Stats(args) => run_stats(args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            e.exit_code()
+        }
+    }
+}
 
-    run_solver_on_path("/home/stefan/stefan-vill-master/tmp_eval/tmp6.opb");
-    //count_with_disconnected_components("/home/stefan/stefan-vill-master/tmp_eval/tmp5.opb")
+fn read_file(path: &PathBuf) -> Result<File, CliError> {
+    parse_file(path).map_err(|e| match e {
+        Error::Io(e) => CliError::Io(path.clone(), e),
+        Error::Parse(e) => CliError::Parse(path.clone(), Box::new(e)),
+    })
+}
 
-    /*
-    let unparsed_file = fs::read_to_string("/home/stefan/stefan-vill-master/tmp_eval/tmp4.opb").expect("cannot read file");
-    let file = parsing::parser::parse(&unparsed_file);
-    let f = parsing::preprocessing::preprocess_file(file);
-    println!("{}", f.to_pbcount_string());
+fn write_output(output: &Option<PathBuf>, content: &str) -> Result<(), CliError> {
+    match output {
+        Some(path) => fs::write(path, content).map_err(|e| CliError::Io(path.clone(), e)),
+        None => write_stdout(|out| out.write_all(content.as_bytes())),
+    }
+}
 
-     */
+/// Writes to the locked stdout. A closed stdout, for example of a pipe into head, ends the output
+/// without an error, other write errors are io errors.
+fn write_stdout(write: impl FnOnce(&mut io::StdoutLock) -> io::Result<()>) -> Result<(), CliError> {
+    let mut out = io::stdout().lock();
+    stdout_result(write(&mut out).and_then(|()| out.flush()))
+}
 
+fn stdout_result(result: io::Result<()>) -> Result<(), CliError> {
+    match result {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(CliError::Io(PathBuf::from("stdout"), e)),
+        _ => Ok(()),
+    }
 }
 
-fn count_with_disconnected_components_on_string(content: String) {
-    let file = parsing::parser::parse(&content);
-    let mut cache_count = HashMap::with_capacity(100_000_000);
-    let f = parsing::preprocessing::preprocess_file(file);
-    let pb_formula = solving::pb_ds::new(&f);
-    use std::time::Instant;
+fn run_count(args: CountArgs) -> Result<(), CliError> {
+    let file = read_file(&args.input)?;
+    let projection = if args.projected { file.projection.clone() } else { None };
+    let (file, report) = preprocess_with_report(file);
+    // substituted variables pass their weights on to their representatives
+    let weights = LiteralWeights::from_file(&file);
+    let pb_formula = build_formula(&file);
+    let options = CountOptions {
+        engine: args.engine.into(),
+        cache_memory: args.cache_memory.saturating_mul(1 << 20),
+        progress: (args.progress_end > args.progress_start).then_some((args.progress_start, args.progress_end)),
+        projection,
+        threads: args.threads,
+        branching: args.heuristic.into(),
+    };
+
     let now = Instant::now();
-    let n = pb_formula.n;
-    let res =  count_disconnected_components(pb_formula, n, 0, 100, &mut cache_count);
+    let (res, approximation, stats) = if args.weighted {
+        let (res, stats) = count_weighted_with_stats(pb_formula, weights, &options);
+        let approximation = res.to_f64();
+        (res.to_string(), approximation, stats)
+    } else {
+        let (res, stats) = count_weighted_with_stats(pb_formula, Unweighted, &options);
+        (res.to_string(), None, stats)
+    };
+    let cache = stats.cache;
+    let search = stats.search;
     let elapsed = now.elapsed();
-    println!("{}\nin {} s", res, elapsed.as_secs());
+
+    write_stdout(|out| match args.format {
+        OutputFormat::Text => {
+            writeln!(out, "{}", res)?;
+            if let Some(approximation) = approximation {
+                writeln!(out, "~ {:e}", approximation)?;
+            }
+            writeln!(out, "in {} s", elapsed.as_secs())?;
+            writeln!(out, "cache: {}", cache)?;
+            writeln!(out, "search: {}", search)?;
+            writeln!(out, "preprocessing: {}", report)
+        }
+        OutputFormat::Json => {
+            let approximation = match approximation {
+                Some(a) => format!(", \"approximation\": {:e}", a),
+                None => String::new(),
+            };
+            writeln!(out, "{{\"count\": \"{}\"{}, \"seconds\": {}, \"cache\": {{\"hits\": {}, \"misses\": {}, \"evictions\": {}, \"invalidated\": {}, \"entries\": {}, \"memory\": {}}}, \"conflicts\": {}, \"learned\": {}, \"backjumps\": {}, \"preprocessing\": {{\"satisfied\": {}, \"duplicates\": {}, \"subsumed\": {}, \"unsatisfiable\": {}, \"fixed\": {}, \"substituted\": {}}}}}",
+                res, approximation, elapsed.as_secs_f64(), cache.hits, cache.misses, cache.evictions, cache.invalidated, cache.entries, cache.memory,
+                search.conflicts, search.learned, search.backjumps,
+                report.satisfied, report.duplicates, report.subsumed, report.unsatisfiable, report.fixed, report.substituted)
+        }
+    })
 }
 
-fn count_with_disconnected_components(path: &str) {
-    let unparsed_file = fs::read_to_string(path).expect("cannot read file");
-    let file = parsing::parser::parse(&unparsed_file);
-    let mut cache_count = HashMap::with_capacity(100_000_000);
-    let f = parsing::preprocessing::preprocess_file(file);
-    let pb_formula = solving::pb_ds::new(&f);
-    use std::time::Instant;
-    let now = Instant::now();
-    let n = pb_formula.n;
-    let res =  count_disconnected_components(pb_formula, n, 0, 100, &mut cache_count);
-    let elapsed = now.elapsed();
-    println!("{}\nin {} s", res, elapsed.as_secs());
+fn run_compile(args: CompileArgs) -> Result<(), CliError> {
+    let file = read_file(&args.input)?;
+    let projection = if args.projected { file.projection.clone() } else { None };
+    let file = preprocess(file);
+    let options = CountOptions {
+        engine: args.engine.into(),
+        cache_memory: args.cache_memory.saturating_mul(1 << 20),
+        projection,
+        branching: args.heuristic.into(),
+        ..CountOptions::default()
+    };
+    let ddnnf = compile(build_formula(&file), &file, &options);
+    eprintln!("{} nodes, {} edges", ddnnf.nodes.len(), ddnnf.edges());
+    write_output(&args.output, &ddnnf.to_string())
+}
+
+fn run_query(args: QueryArgs) -> Result<(), CliError> {
+    let file = read_file(&args.input)?;
+    let mut assumptions = Vec::new();
+    for literal in &args.assume {
+        let name = literal.strip_prefix('~').unwrap_or(literal);
+        match file.name_map.get_by_left(name) {
+            Some(v) => assumptions.push((*v, !literal.starts_with('~'))),
+            None => return Err(CliError::Usage(format!("unknown variable `{}` in --assume", name))),
+        }
+    }
+    // auxiliary variables of the preprocessing are not listed
+    let variables = file.variable_count();
+    let projection = if args.projected { file.projection.clone() } else { None };
+    // the circuit of a projection does not contain the other variables
+    if let Some((v, _)) = assumptions.iter().find(|(v, _)| projection.as_ref().is_some_and(|p| !p.contains(v))) {
+        let name = file.name_map.get_by_right(v).unwrap();
+        return Err(CliError::Usage(format!("cannot assume `{}`, it is not in the projection", name)));
+    }
+    let file = preprocess(file);
+    let options = CountOptions {
+        engine: args.engine.into(),
+        cache_memory: args.cache_memory.saturating_mul(1 << 20),
+        projection,
+        branching: args.heuristic.into(),
+        ..CountOptions::default()
+    };
+    let ddnnf = compile(build_formula(&file), &file, &options);
+
+    let (total, literals) = if args.weighted {
+        let weights = LiteralWeights::from_file(&file);
+        query_counts(&ddnnf, &weights, &assumptions, &file, variables, |p, t| (p / t).to_f64())
+    } else {
+        query_counts(&ddnnf, &Unweighted, &assumptions, &file, variables, |p, t| BigRational::new(p.into(), t.into()).to_f64())
+    };
+    write_stdout(|out| match args.format {
+        OutputFormat::Text => {
+            writeln!(out, "{}", total)?;
+            for (name, positive, negative, marginal) in literals {
+                match marginal {
+                    Some(m) => writeln!(out, "{} {} {} {}", name, positive, negative, m)?,
+                    None => writeln!(out, "{} {} {}", name, positive, negative)?,
+                }
+            }
+            Ok(())
+        }
+        OutputFormat::Json => {
+            let entries: Vec<String> = literals.iter().map(|(name, positive, negative, marginal)| {
+                let marginal = marginal.map_or("null".to_string(), |m| m.to_string());
+                format!("{{\"name\": \"{}\", \"positive\": \"{}\", \"negative\": \"{}\", \"marginal\": {}}}", name, positive, negative, marginal)
+            }).collect();
+            writeln!(out, "{{\"count\": \"{}\", \"nodes\": {}, \"literals\": [{}]}}", total, ddnnf.nodes.len(), entries.join(", "))
+        }
+    })
 }
 
-fn run_solver_on_path(path: &str){
-    let unparsed_file = fs::read_to_string(path).expect("cannot read file");
-    //let unparsed_file = fs::read_to_string("test.csv").expect("cannot read file");
-    let file = parsing::parser::parse(&unparsed_file);
-    run_solver_on_file(file);
+/// Name of a variable, the counts of its positive and negative literal and the marginal of the positive literal.
+type LiteralCount = (String, String, String, Option<f64>);
+
+/// Count under the assumptions and the counts of the literals of every named variable below `variables`
+/// together with the marginal of the positive literal, `ratio` divides two counts.
+fn query_counts<W: Weights>(ddnnf: &Ddnnf, weights: &W, assumptions: &[(u32, bool)], file: &File, variables: u32, ratio: fn(W::Value, W::Value) -> Option<f64>) -> (String, Vec<LiteralCount>) {
+    let total = ddnnf.count_weighted(weights, assumptions);
+    let mut literals = Vec::new();
+    for (v, counts) in ddnnf.literal_counts(weights, assumptions).into_iter().enumerate().take(variables as usize) {
+        // declared variables without a name occur in no constraint
+        if let (Some((positive, negative)), Some(name)) = (counts, file.name_map.get_by_right(&(v as u32))) {
+            let marginal = if total.is_zero() { None } else { ratio(positive.clone(), total.clone()) };
+            literals.push((name.clone(), positive.to_string(), negative.to_string(), marginal));
+        }
+    }
+    (total.to_string(), literals)
 }
 
-fn run_solver_on_string(string: &str){
-    let file = parsing::parser::parse(string);
-    run_solver_on_file(file);
+fn run_sample(args: SampleArgs) -> Result<(), CliError> {
+    let mut file = read_file(&args.input)?;
+    file.name_declared_variables();
+    // auxiliary variables of the preprocessing are not printed
+    let variables = file.variable_count();
+    let projection = if args.projected { file.projection.clone() } else { None };
+    let file = preprocess(file);
+    let options = CountOptions {
+        engine: args.engine.into(),
+        cache_memory: args.cache_memory.saturating_mul(1 << 20),
+        projection,
+        branching: args.heuristic.into(),
+        ..CountOptions::default()
+    };
+    let ddnnf = compile(build_formula(&file), &file, &options);
+    let Some(mut sampler) = Sampler::new(&ddnnf, &[]) else {
+        eprintln!("the formula is unsatisfiable");
+        return Ok(());
+    };
+    sampler.variables = variables;
+    let seed = args.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    eprintln!("seed {}", seed);
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let named = |model: Vec<(u32, bool)>| -> Vec<(String, bool)> {
+        model.into_iter().map(|(v, b)| (file.name_map.get_by_right(&v).unwrap().clone(), b)).collect()
+    };
+    write_stdout(|out| match args.format {
+        OutputFormat::Text => {
+            for _ in 0..args.samples {
+                let literals: Vec<String> = named(sampler.sample(&mut rng)).into_iter()
+                    .map(|(name, b)| format!("{}{}", if b { "" } else { "~" }, name))
+                    .collect();
+                writeln!(out, "{}", literals.join(" "))?;
+            }
+            Ok(())
+        }
+        OutputFormat::Json => {
+            let samples: Vec<String> = sampler.samples(&mut rng, args.samples).into_iter().map(|model| {
+                let entries: Vec<String> = named(model).into_iter().map(|(name, b)| format!("\"{}\": {}", name, b)).collect();
+                format!("{{{}}}", entries.join(", "))
+            }).collect();
+            writeln!(out, "[{}]", samples.join(", "))
+        }
+    })
 }
 
-fn run_solver_on_file(file: File){
-    let mut cache_count = HashMap::with_capacity(100);
-    let f = parsing::preprocessing::preprocess_file(file);
-    //println!("{}", f.to_pbcount_string());
-    let pb_formula = solving::pb_ds::new(&f);
-    use std::time::Instant;
+fn run_enumerate(args: EnumerateArgs) -> Result<(), CliError> {
+    let mut file = read_file(&args.input)?;
+    file.name_declared_variables();
+    let variables = file.variable_count();
+    let shown = if args.projected { file.projection.clone() } else { None };
+    let file = preprocess(file);
+    let enumerator = Enumerator {
+        projection: shown.map(|s| s.into_iter().collect()),
+        expand_free: !args.dont_cares,
+        expanded: HashSet::new(),
+        limit: args.limit,
+        // auxiliary variables of the preprocessing would only repeat assignments
+        variables: Some(variables),
+        substitutions: file.substitutions.clone(),
+    };
+
+    let mut out = io::stdout().lock();
+    let mut written = Ok(());
     let now = Instant::now();
-    let res = solving::solver::count(&pb_formula, pb_formula.n, 0, 100, &mut cache_count);
-    let elapsed = now.elapsed();
-    println!("{}\nin {} s", res, elapsed.as_secs());
-    //println!("cache:\n{:?}", cache_count);
+    let listed_count = enumerator.enumerate(&build_formula(&file), |model| {
+        let name = |v: &u32| file.name_map.get_by_right(v).unwrap();
+        let line = match args.format {
+            OutputFormat::Text => model.iter().map(|(v, b)| match b {
+                Some(true) => name(v).clone(),
+                Some(false) => format!("~{}", name(v)),
+                None => format!("*{}", name(v)),
+            }).collect::<Vec<String>>().join(" "),
+            OutputFormat::Json => {
+                let entries: Vec<String> = model.iter().map(|(v, b)| format!("\"{}\": {}", name(v), b.map_or("null".to_string(), |b| b.to_string()))).collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+        };
+        // a failed write, for example to the closed pipe of head, ends the enumeration
+        written = writeln!(out, "{}", line);
+        written.is_ok()
+    });
+    eprintln!("{} assignments in {} s", listed_count, now.elapsed().as_secs());
+    stdout_result(written.and_then(|()| out.flush()))
 }
 
-fn print_file_as_pbcount_string(file: File){
-    println!("{}", file.to_pbcount_string());
+fn format_file(file: &File, format: ConvertFormat, encoding: CnfEncoding) -> String {
+    match format {
+        ConvertFormat::Opb => file.to_string(),
+        ConvertFormat::Pbcount => file.to_pbcount_string(),
+        ConvertFormat::Dimacs => file.to_dimacs_string(encoding.into()),
+    }
 }
 
+fn run_convert(args: ConvertArgs) -> Result<(), CliError> {
+    let mut file = read_file(&args.input)?;
+    if args.preprocess {
+        let (preprocessed, report) = preprocess_with_report(file);
+        eprintln!("preprocessing: {}", report);
+        file = preprocessed;
+    }
+    write_output(&args.output, &format_file(&file, args.to, args.encoding))
+}
 
+fn run_generate(args: GenerateArgs) -> Result<(), CliError> {
+    let file = generate_pb_formula(args.equations, args.variables, args.size, args.max_factor).map_err(CliError::Usage)?;
+    write_output(&args.output, &format_file(&file, args.to, args.encoding))
+}
+
+fn run_stats(args: StatsArgs) -> Result<(), CliError> {
+    let file = read_file(&args.input)?;
+
+    let mut symbols = [0; 3];
+    let mut literals = 0;
+    let mut products = 0;
+    let mut max_length = 0;
+    let mut max_factor = 0;
+    for equation in &file.equations {
+        match equation.symbol {
+            EquationSymbol::eq => symbols[0] += 1,
+            EquationSymbol::ge => symbols[1] += 1,
+            EquationSymbol::le => symbols[2] += 1,
+        }
+        let mut length = 0;
+        for part in equation.e1.literals.iter().chain(equation.e2.literals.iter()) {
+            match part {
+                EquationPart::Literal { factor, .. } => {
+                    length += 1;
+                    max_factor = max_factor.max(factor.abs());
+                }
+                EquationPart::Product { factor, .. } => {
+                    length += 1;
+                    products += 1;
+                    max_factor = max_factor.max(factor.abs());
+                }
+                EquationPart::Factor(_) => {}
+            }
+        }
+        literals += length;
+        max_length = max_length.max(length);
+    }
+
+    let variables = file.variable_count() as usize;
+    let weights = file.weights.len();
+    let shown = file.projection.as_ref().map_or(0, |p| p.len());
+    let constraints = file.equations.len();
+    let pb_formula = build_formula(&preprocess(file));
+    let preprocessed_constraints = pb_formula.clauses.len();
+    let treewidth = VariableOrder::new(&pb_formula, EliminationHeuristic::MinFill).width;
+    let components = formula_to_disconnected_formula(pb_formula, None).partitions.len();
+
+    let stats = [
+        ("variables", variables),
+        ("constraints", constraints),
+        ("equalities", symbols[0]),
+        ("greater_equal", symbols[1]),
+        ("less_equal", symbols[2]),
+        ("literals", literals),
+        ("products", products),
+        ("weights", weights),
+        ("shown_variables", shown),
+        ("max_constraint_length", max_length),
+        ("max_coefficient", max_factor as usize),
+        ("preprocessed_constraints", preprocessed_constraints),
+        ("components", components),
+        ("treewidth_upper_bound", treewidth),
+    ];
+    write_stdout(|out| match args.format {
+        OutputFormat::Text => {
+            for (name, value) in stats {
+                writeln!(out, "{}: {}", name, value)?;
+            }
+            Ok(())
+        }
+        OutputFormat::Json => {
+            let entries: Vec<String> = stats.iter().map(|(name, value)| format!("\"{}\": {}", name, value)).collect();
+            writeln!(out, "{{{}}}", entries.join(", "))
+        }
+    })
+}
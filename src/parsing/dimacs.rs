@@ -0,0 +1,204 @@
+//! Reader for DIMACS CNF in the format of the model counting competition.
+//!
+//! Variable `i` is named `xi`, the same names the PBCount format uses. Every clause becomes an
+//! equation `sum of literals >= 1`, their number has to match the header. `c p show` lines declare
+//! the projection and `c p weight` lines literal weights.
+
+use bimap::BiHashMap;
+use crate::parsing::equation_ds::{Equation, EquationPart, EquationSide, EquationSymbol, File, LiteralWeight};
+use crate::parsing::parser::{parse_weight, ParseError};
+
+/// Whether the first line that is not a comment is a `p cnf` header.
+pub fn is_dimacs(content: &str) -> bool {
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('c') {
+            continue;
+        }
+        let mut tokens = line.split_whitespace();
+        return tokens.next() == Some("p") && tokens.next() == Some("cnf");
+    }
+    false
+}
+
+pub fn parse(content: &str) -> Result<File, ParseError> {
+    let mut file = File{
+        equations: Vec::new(),
+        name_map: BiHashMap::new(),
+        name_index: 0,
+        declared_variables: None,
+        objective: None,
+        weights: Vec::new(),
+        projection: None,
+        substitutions: Vec::new()
+    };
+    let mut clause: Vec<i64> = Vec::new();
+    // clauses declared by the header, with an error at their position in case fewer or more are read
+    let mut declared_clauses = None;
+
+    for (number, line) in content.lines().enumerate() {
+        let tokens = tokens(line);
+        let Some((column, first)) = tokens.first().copied() else {
+            continue;
+        };
+        let error = |column: usize, token: &str, message: String| ParseError {
+            line: number + 1,
+            column,
+            line_content: line.to_string(),
+            token: token.to_string(),
+            expected: Vec::new(),
+            message,
+        };
+
+        if first == "c" {
+            let kind: Vec<&str> = tokens.iter().skip(1).take(2).map(|(_, t)| *t).collect();
+            match kind[..] {
+                ["p", "show"] => {
+                    let mut projection = file.projection.take().unwrap_or_default();
+                    for (column, token) in &tokens[3..] {
+                        let literal = parse_literal(&file, token).map_err(|m| error(*column, token, m))?;
+                        if literal == 0 {
+                            break;
+                        }
+                        if literal < 0 {
+                            return Err(error(*column, token, format!("negative literal `{}` in a show line, it lists variables", token)));
+                        }
+                        let index = literal as u32 - 1;
+                        if !projection.contains(&index) {
+                            projection.push(index);
+                        }
+                    }
+                    file.projection = Some(projection);
+                }
+                ["p", "weight"] => {
+                    let Some((column, token)) = tokens.get(3).copied() else {
+                        return Err(error(line.len() + 1, "", "missing literal of the weight".to_string()));
+                    };
+                    let literal = parse_literal(&file, token).map_err(|m| error(column, token, m))?;
+                    if literal == 0 {
+                        return Err(error(column, token, "weight of literal 0".to_string()));
+                    }
+                    let Some((column, token)) = tokens.get(4).copied() else {
+                        return Err(error(line.len() + 1, "", "missing weight".to_string()));
+                    };
+                    let weight = parse_weight(token).ok_or_else(|| error(column, token, format!("invalid weight `{}`", token)))?;
+                    file.weights.push(LiteralWeight {
+                        name: literal.unsigned_abs() as u32 - 1,
+                        negated: literal < 0,
+                        weight
+                    });
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if first.starts_with('c') {
+            continue;
+        }
+        if first == "p" {
+            if file.declared_variables.is_some() {
+                return Err(error(column, first, "second `p cnf` header".to_string()));
+            }
+            let (variables, clauses) = match tokens.get(1..) {
+                Some([(_, "cnf"), (column, variables), (clauses_column, clauses)]) => (
+                    variables.parse::<u32>().map_err(|_| error(*column, variables, format!("invalid number of variables `{}`", variables)))?,
+                    clauses.parse::<usize>().map_err(|_| error(*clauses_column, clauses, format!("invalid number of clauses `{}`", clauses)))?,
+                ),
+                _ => return Err(error(column, first, "expected `p cnf <variables> <clauses>`".to_string())),
+            };
+            let (column, token) = tokens[3];
+            declared_clauses = Some((clauses, error(column, token, String::new())));
+            for v in 0..variables {
+                file.name_map.insert(format!("x{}", v + 1), v);
+            }
+            file.name_index = variables;
+            file.declared_variables = Some(variables);
+            continue;
+        }
+        // SATLIB files end with a line `%` followed by `0`
+        if first == "%" {
+            break;
+        }
+        if file.declared_variables.is_none() {
+            return Err(error(column, first, "clause before the `p cnf` header".to_string()));
+        }
+        for (column, token) in &tokens {
+            let literal = parse_literal(&file, token).map_err(|m| error(*column, token, m))?;
+            if literal == 0 {
+                file.equations.push(clause_equation(&clause));
+                clause.clear();
+            } else {
+                clause.push(literal);
+            }
+        }
+    }
+    // the last clause may miss its terminating 0
+    if !clause.is_empty() {
+        file.equations.push(clause_equation(&clause));
+    }
+    if let Some((clauses, mut error)) = declared_clauses {
+        if clauses != file.equations.len() {
+            error.message = format!("the header declares {} clauses, the file contains {}", clauses, file.equations.len());
+            return Err(error);
+        }
+    }
+    if file.declared_variables.is_none() {
+        return Err(ParseError {
+            line: content.lines().count().max(1),
+            column: 1,
+            line_content: content.lines().last().unwrap_or("").to_string(),
+            token: String::new(),
+            expected: vec!["`p cnf` header".to_string()],
+            message: "missing `p cnf` header".to_string(),
+        });
+    }
+    Ok(file)
+}
+
+/// Parses a literal and checks it against the number of variables of the header.
+fn parse_literal(file: &File, token: &str) -> Result<i64, String> {
+    let Some(variables) = file.declared_variables else {
+        return Err("literal before the `p cnf` header".to_string());
+    };
+    let literal: i64 = token.parse().map_err(|_| format!("invalid literal `{}`", token))?;
+    if literal.unsigned_abs() > variables as u64 {
+        return Err(format!("literal `{}` exceeds the {} variables of the header", token, variables));
+    }
+    Ok(literal)
+}
+
+/// `l1 + ... + lk >= 1`, the empty clause becomes `0 >= 1`.
+fn clause_equation(clause: &[i64]) -> Equation {
+    let mut literals: Vec<EquationPart> = clause.iter().map(|l| EquationPart::Literal {
+        factor: 1,
+        name: l.unsigned_abs() as u32 - 1,
+        negated: *l < 0
+    }).collect();
+    if literals.is_empty() {
+        literals.push(EquationPart::Factor(0));
+    }
+    Equation{
+        symbol: EquationSymbol::ge,
+        e1: EquationSide{ literals },
+        e2: EquationSide{ literals: vec![EquationPart::Factor(1)] }
+    }
+}
+
+/// Whitespace separated tokens of the line with their 1-based column.
+fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut result = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices() {
+        if c.is_whitespace() {
+            if let Some(s) = start.take() {
+                result.push((s + 1, &line[s..i]));
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        result.push((s + 1, &line[s..]));
+    }
+    result
+}
@@ -1,58 +1,144 @@
-use std::collections::HashSet;
 use std::fmt::{Display, Formatter, Write};
 use bimap::BiMap;
+use num_rational::BigRational;
+use crate::encoding::cnf;
+use crate::encoding::cnf::CnfEncoding;
 
+#[derive(Clone)]
 pub struct File {
     pub name_map: BiMap<String, u32>,
     pub equations: Vec<Equation>,
-    pub name_index: u32
+    pub name_index: u32,
+    /// Number of variables declared in the `* #variable= N` header, variables that never appear in an equation are free.
+    pub declared_variables: Option<u32>,
+    pub objective: Option<Objective>,
+    /// Literal weights declared with `* weight`, used for weighted model counting.
+    pub weights: Vec<LiteralWeight>,
+    /// Variables declared with `* show`, the model count is projected on them if present.
+    pub projection: Option<Vec<u32>>,
+    /// Variables that preprocessing replaced by an equivalent literal, they are no longer part of the equations.
+    pub substitutions: Vec<Substitution>
 }
 
 impl Display for File {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.to_string())
+        let substitutions = self.substitution_equations();
+        if self.declared_variables.is_some() {
+            writeln!(f, "* #variable= {} #constraint= {}", self.variable_count(), self.equations.len() + substitutions.len())?;
+        }
+        for w in &self.weights {
+            writeln!(f, "* weight {}{} {}", if w.negated { "~" } else { "" }, self.name_map.get_by_right(&w.name).unwrap(), w.weight)?;
+        }
+        if let Some(projection) = &self.projection {
+            write!(f, "* show")?;
+            for v in projection {
+                write!(f, " {}", self.name_map.get_by_right(v).unwrap())?;
+            }
+            writeln!(f)?;
+        }
+        if let Some(objective) = &self.objective {
+            writeln!(f, "{} {};", objective.sense, objective.side.to_string(self))?;
+        }
+        for e in self.equations.iter().chain(&substitutions) {
+            writeln!(f, "{}", e.to_string(self))?;
+        }
+        Ok(())
     }
 }
 
 impl File {
-    pub fn to_string(&self) -> String {
-        let mut result = String::new();
-        for e in &self.equations {
-            result.push_str(&*e.to_string(&self));
-            result.push_str("\n");
+    /// Adds a new variable whose name starts with `prefix` and does not clash with an existing name.
+    pub fn fresh_variable(&mut self, prefix: &str) -> u32 {
+        let mut suffix = self.name_index;
+        let mut name = format!("{}{}", prefix, suffix);
+        while self.name_map.contains_left(&name) {
+            suffix += 1;
+            name = format!("{}{}", prefix, suffix);
         }
-        result
+        // declared but unused variables keep their indices and stay free
+        let index = self.variable_count();
+        self.name_map.insert(name, index);
+        self.name_index = index + 1;
+        if let Some(declared) = self.declared_variables.as_mut() {
+            *declared = index + 1;
+        }
+        index
+    }
+
+    /// Names the declared variables that appear in no equation `x1`, `x2`, ... skipping names that are taken,
+    /// so every variable can be printed.
+    pub fn name_declared_variables(&mut self) {
+        let mut number = 1;
+        for v in 0..self.variable_count() {
+            if self.name_map.contains_right(&v) {
+                continue;
+            }
+            while self.name_map.contains_left(&format!("x{}", number)) {
+                number += 1;
+            }
+            self.name_map.insert(format!("x{}", number), v);
+        }
+    }
+
+    /// Number of variables of the formula, including declared variables that do not appear in any equation.
+    pub fn variable_count(&self) -> u32 {
+        let used = self.name_map.len() as u32;
+        match self.declared_variables {
+            Some(declared) => declared.max(used),
+            None => used
+        }
+    }
+
+    /// Equations `x - y = 0` or `x + y = 1` that restore the substituted variables, so the
+    /// written formula has the same models as the original one.
+    pub fn substitution_equations(&self) -> Vec<Equation> {
+        self.substitutions.iter().map(|s| Equation{
+            symbol: EquationSymbol::eq,
+            e1: EquationSide{
+                literals: vec![
+                    EquationPart::Literal { factor: 1, name: s.name, negated: false },
+                    EquationPart::Literal { factor: if s.negated { 1 } else { -1 }, name: s.literal, negated: false },
+                ]
+            },
+            e2: EquationSide{ literals: vec![EquationPart::Factor(if s.negated { 1 } else { 0 })] }
+        }).collect()
     }
 
     pub fn to_pbcount_string(&self) -> String {
+        let substitutions = self.substitution_equations();
         let mut result = String::new();
         result.push_str("* #variable= ");
-        result.push_str(&*self.name_map.len().to_string());
+        result.push_str(&self.variable_count().to_string());
         result.push_str(" #constraint= ");
-        result.push_str(&*self.equations.len().to_string());
-        result.push_str("\n");
-        for e in &self.equations {
-            result.push_str(&*e.to_pbcount_string(&self));
+        result.push_str(&(self.equations.len() + substitutions.len()).to_string());
+        result.push('\n');
+        for w in &self.weights {
+            result.push_str(&format!("* weight {}x{} {}\n", if w.negated { "~" } else { "" }, w.name + 1, w.weight));
+        }
+        if let Some(projection) = &self.projection {
+            result.push_str("* show");
+            for v in projection {
+                result.push_str(&format!(" x{}", v + 1));
+            }
+            result.push('\n');
+        }
+        if let Some(objective) = &self.objective {
+            result.push_str(&format!("{} {};\n", objective.sense, objective.side.to_pbcount_string(self)));
+        }
+        for e in self.equations.iter().chain(&substitutions) {
+            result.push_str(&e.to_pbcount_string(self));
             result.push_str(";\n");
         }
         result
     }
 
-    pub fn to_dimacs_string(&self) -> String {
-        let mut result = String::new();
-        result.push_str("p cnf ");
-        result.push_str(&*self.name_map.len().to_string());
-        result.push_str(" ");
-        result.push_str(&*self.equations.len().to_string());
-        result.push_str("\n");
-        for e in &self.equations {
-            result.push_str(&*e.to_dimacs_string());
-            result.push_str("\n");
-        }
-        result
+    /// Encodes the formula into DIMACS CNF, see [`crate::encoding::cnf`].
+    pub fn to_dimacs_string(&self, encoding: CnfEncoding) -> String {
+        cnf::encode_file(self, encoding)
     }
 }
 
+#[derive(Clone)]
 pub struct Equation {
     pub e1: EquationSide,
     pub e2: EquationSide,
@@ -71,12 +157,46 @@ impl Equation {
     fn to_pbcount_string(&self, file: &File) -> String {
         format!("{} {} {}", self.e1.to_pbcount_string(file), self.symbol, self.e2.to_pbcount_string(file))
     }
-    fn to_dimacs_string(&self) -> String {
-        format!("{} 0", self.e1.to_dimacs_string())
+}
+
+#[derive(Clone)]
+pub struct LiteralWeight {
+    pub name: u32,
+    pub negated: bool,
+    pub weight: BigRational
+}
+
+/// Variable `name` that equals the literal `literal`, or its negation if `negated`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Substitution {
+    pub name: u32,
+    pub literal: u32,
+    pub negated: bool
+}
+
+#[derive(Clone)]
+pub struct Objective {
+    pub sense: ObjectiveSense,
+    pub side: EquationSide
+}
+
+#[derive(Clone, PartialEq)]
+pub enum ObjectiveSense {
+    Minimize,
+    Maximize
+}
+
+impl Display for ObjectiveSense {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjectiveSense::Minimize => write!(f, "min:"),
+            ObjectiveSense::Maximize => write!(f, "max:")
+        }
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
+#[allow(non_camel_case_types)]
 pub enum EquationSymbol {
     eq,
     ge,
@@ -101,14 +221,15 @@ pub struct EquationSide {
 
 impl Display for EquationSide {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        Ok(for (i,l) in self.literals.iter().enumerate() {
+        for (i,l) in self.literals.iter().enumerate() {
             if i == 0{
-                write!(f, "{}", l);
+                write!(f, "{}", l)?;
             }else{
-                write!(f, " {}", l);
+                write!(f, " {}", l)?;
             }
 
-        })
+        }
+        Ok(())
     }
 }
 impl EquationSide {
@@ -140,28 +261,27 @@ impl EquationSide {
         result
     }
 
-    fn to_dimacs_string(&self) -> String {
-        let mut result = String::new();
-        for (i,l) in self.literals.iter().enumerate() {
-            if i == 0{
-                write!(&mut result, "{}", l.to_dimacs_string()).unwrap();
-            }else{
-                write!(&mut result, " {}", l.to_dimacs_string()).unwrap();
-            }
-
-        }
-
-        result
-    }
 }
 
 #[derive(Clone)]
 pub enum EquationPart {
     Literal {
-        factor: i32,
+        factor: i64,
         name: u32,
+        negated: bool,
     },
-    Factor(i32)
+    /// Coefficient times the conjunction of at least two literals.
+    Product {
+        factor: i64,
+        literals: Vec<ProductLiteral>,
+    },
+    Factor(i64)
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProductLiteral {
+    pub name: u32,
+    pub negated: bool,
 }
 
 impl Display for EquationPart {
@@ -173,7 +293,14 @@ impl Display for EquationPart {
                 } else {
                     write!(f, "+{}", i)}
             },
-            EquationPart::Literal {factor, name} => write!(f, "{}*{}", factor, name)
+            EquationPart::Literal {factor, name, negated} => write!(f, "{}*{}{}", factor, if *negated { "~" } else { "" }, name),
+            EquationPart::Product {factor, literals} => {
+                write!(f, "{}", factor)?;
+                for l in literals {
+                    write!(f, "*{}{}", if l.negated { "~" } else { "" }, l.name)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -187,19 +314,29 @@ impl EquationPart {
                 } else {
                     write!(result, "+{}", i).unwrap();}
             },
-            EquationPart::Literal {factor, name} => {
-                let tmp = file.name_map.get_by_right(name);
+            EquationPart::Literal {factor, name, negated} => {
+                let negation = if *negated { "~" } else { "" };
+                if factor < &0 {
+                    write!(result, "{}*{}{}", factor, negation, file.name_map.get_by_right(name).unwrap()).unwrap();
+                } else {
+                    write!(result, "+{}*{}{}", factor, negation, file.name_map.get_by_right(name).unwrap()).unwrap();
+                }
+            }
+            EquationPart::Product {factor, literals} => {
                 if factor < &0 {
-                    write!(result, "{}*{}", factor, file.name_map.get_by_right(name).unwrap()).unwrap();
+                    write!(result, "{}", factor).unwrap();
                 } else {
-                    write!(result, "+{}*{}", factor, file.name_map.get_by_right(name).unwrap()).unwrap();
+                    write!(result, "+{}", factor).unwrap();
+                }
+                for l in literals {
+                    write!(result, " {}{}", if l.negated { "~" } else { "" }, file.name_map.get_by_right(&l.name).unwrap()).unwrap();
                 }
             }
         }
         result
     }
 
-    fn to_pbcount_string(&self, file: &File) -> String {
+    fn to_pbcount_string(&self, _file: &File) -> String {
         let mut result = String::new();
         match self {
             EquationPart::Factor(i) => {
@@ -208,28 +345,22 @@ impl EquationPart {
                 } else {
                     write!(result, "+{}", i).unwrap();}
             },
-            EquationPart::Literal {factor, name} => {
+            EquationPart::Literal {factor, name, negated} => {
+                let negation = if *negated { "~" } else { "" };
                 if factor < &0 {
-                    write!(result, "{} x{}", factor, name +1).unwrap();
+                    write!(result, "{} {}x{}", factor, negation, name +1).unwrap();
                 } else {
-                    write!(result, "+{} x{}", factor, name +1).unwrap();
+                    write!(result, "+{} {}x{}", factor, negation, name +1).unwrap();
                 }
             }
-        }
-        result
-    }
-
-    fn to_dimacs_string(&self) -> String {
-        let mut result = String::new();
-        match self {
-            EquationPart::Factor(i) => {
-
-            },
-            EquationPart::Literal {factor, name} => {
+            EquationPart::Product {factor, literals} => {
                 if factor < &0 {
-                    write!(result, "-{}", name +1).unwrap();
+                    write!(result, "{}", factor).unwrap();
                 } else {
-                    write!(result, "{}", name +1).unwrap();
+                    write!(result, "+{}", factor).unwrap();
+                }
+                for l in literals {
+                    write!(result, " {}x{}", if l.negated { "~" } else { "" }, l.name +1).unwrap();
                 }
             }
         }
@@ -1,12 +1,22 @@
+use std::fmt::{Display, Formatter};
 use bimap::BiHashMap;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::Zero;
+use pest::error::{ErrorVariant, InputLocation, LineColLocation};
 use pest::iterators::Pair;
 use pest::Parser;
 use pest_derive::Parser;
+use crate::parsing::dimacs;
 use crate::parsing::equation_ds::*;
 
-pub fn parse(content: &str) -> File {
+/// Parses a formula, DIMACS CNF is recognized by its `p cnf` header, see [`crate::parsing::dimacs`].
+pub fn parse(content: &str) -> Result<File, ParseError> {
+    if dimacs::is_dimacs(content) {
+        return dimacs::parse(content);
+    }
     let file = TestParser::parse(Rule::file, content)
-        .expect("unsuccessful parse")
+        .map_err(|e| ParseError::from_pest(e, content))?
         .next().unwrap();
     parse_file(file)
 }
@@ -15,34 +25,284 @@ pub fn parse(content: &str) -> File {
 #[grammar = "opb.pest"] // points to the grammar file we created
 struct TestParser;
 
+/// Error returned by [`parse`], positions are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    /// Text of the input line the error was found in.
+    pub line_content: String,
+    /// Input starting at the error position up to the next whitespace, empty at the end of a line.
+    pub token: String,
+    /// Descriptions of the rules that would have been accepted at the error position.
+    pub expected: Vec<String>,
+    pub message: String,
+}
+
+impl ParseError {
+    fn from_pest(error: pest::error::Error<Rule>, content: &str) -> ParseError {
+        let (line, column) = match error.line_col {
+            LineColLocation::Pos(p) => p,
+            LineColLocation::Span(p, _) => p,
+        };
+        let position = match error.location {
+            InputLocation::Pos(p) => p,
+            InputLocation::Span((p, _)) => p,
+        };
+        let token: String = content[position..].chars().take_while(|c| !c.is_whitespace()).collect();
+        let (expected, message) = match &error.variant {
+            ErrorVariant::ParsingError { positives, .. } => {
+                let expected: Vec<String> = positives.iter().map(|r| describe_rule(*r).to_string()).collect();
+                let found = if !token.is_empty() {
+                    format!("`{}`", token)
+                } else if position < content.len() {
+                    "end of line".to_string()
+                } else {
+                    "end of input".to_string()
+                };
+                let message = if expected.is_empty() {
+                    format!("unexpected {}", found)
+                } else {
+                    format!("unexpected {}, expected {}", found, expected.join(" or "))
+                };
+                (expected, message)
+            }
+            ErrorVariant::CustomError { message } => (Vec::new(), message.clone()),
+        };
+        ParseError {
+            line,
+            column,
+            // pest marks the line break of the line with `␊`
+            line_content: error.line().trim_end_matches(['\u{240D}', '\u{240A}']).to_string(),
+            token,
+            expected,
+            message,
+        }
+    }
+
+    fn from_pair(pair: &Pair<Rule>, message: String) -> ParseError {
+        let (line, column) = pair.line_col();
+        ParseError {
+            line,
+            column,
+            line_content: pair.as_span().start_pos().line_of().trim_end_matches(['\r', '\n']).to_string(),
+            token: pair.as_str().to_string(),
+            expected: Vec::new(),
+            message,
+        }
+    }
+
+    /// Renders the error together with the offending line and a marker below the error position.
+    pub fn annotated(&self) -> String {
+        let number = self.line.to_string();
+        let padding = " ".repeat(number.len());
+        let marker_length = self.token.chars().count().max(1);
+        format!("{}:{}: {}\n{} |\n{} | {}\n{} | {}{}",
+                self.line, self.column, self.message,
+                padding,
+                number, self.line_content,
+                padding, " ".repeat(self.column - 1), "^".repeat(marker_length))
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn describe_rule(rule: Rule) -> &'static str {
+    match rule {
+        Rule::factor | Rule::factor_value => "number",
+        Rule::factor_sign => "sign",
+        Rule::var_name => "variable name",
+        Rule::negation => "`~`",
+        Rule::first_literal | Rule::literal | Rule::implicit_one_literal => "term",
+        Rule::equation_symbol => "`=`, `<=` or `>=`",
+        Rule::equation_side => "equation side",
+        Rule::equation => "equation",
+        Rule::objective | Rule::objective_sense => "objective",
+        Rule::header => "header",
+        Rule::constraint_count => "`#constraint=`",
+        Rule::weight | Rule::weight_value => "weight",
+        Rule::show => "show declaration",
+        Rule::EOI => "end of input",
+        _ => "input",
+    }
+}
 
-fn parse_file(rule: Pair<Rule>) -> File {
+fn parse_number<T: std::str::FromStr>(rule: &Pair<Rule>) -> Result<T, ParseError> {
+    rule.as_str().trim().parse()
+        .map_err(|_| ParseError::from_pair(rule, format!("number `{}` is out of range", rule.as_str().trim())))
+}
+
+/// Coefficients and degrees are limited to the range of `i32`, so the sums and slacks of a constraint
+/// that preprocessing and the solver compute in `i64` cannot overflow.
+fn parse_coefficient(rule: &Pair<Rule>) -> Result<i64, ParseError> {
+    rule.as_str().trim().parse::<i32>().map(i64::from)
+        .map_err(|_| ParseError::from_pair(rule, format!("number `{}` is out of range, at most {} is supported", rule.as_str().trim(), i32::MAX)))
+}
+
+fn parse_file(rule: Pair<Rule>) -> Result<File, ParseError> {
     let mut file = File{
         equations: Vec::new(),
         name_map: BiHashMap::new(),
-        name_index: 0
+        name_index: 0,
+        declared_variables: None,
+        objective: None,
+        weights: Vec::new(),
+        projection: None,
+        substitutions: Vec::new()
     };
+    let mut annotations = Vec::new();
     for inner_rule in rule.into_inner(){
         match inner_rule.as_rule() {
+            Rule::header => {
+                let variables = inner_rule.into_inner().next().unwrap();
+                file.declared_variables = Some(parse_number(&variables)?);
+            }
+            // names of weights and show declarations are only known once all constraints are read
+            Rule::weight | Rule::show => annotations.push(inner_rule),
+            Rule::objective => {
+                file.objective = Some(parse_objective(inner_rule, &mut file)?);
+            }
             Rule::equation=> {
-                let tmp = parse_equation(inner_rule, &mut file);
+                let tmp = parse_equation(inner_rule, &mut file)?;
                 file.equations.push(tmp);
             }
             Rule::EOI => (),
             _ => unreachable!()
         }
     }
-    file
+    for annotation in annotations {
+        let names: Vec<Pair<Rule>> = annotation.clone().into_inner().filter(|r| r.as_rule() == Rule::var_name).collect();
+        check_known_names(&names, &file)?;
+        if annotation.as_rule() == Rule::weight {
+            let weight = parse_literal_weight(annotation, &mut file)?;
+            file.weights.push(weight);
+        } else {
+            let mut projection = file.projection.take().unwrap_or_default();
+            for name in names {
+                let index = variable_index(name.as_str(), &mut file);
+                if !projection.contains(&index) {
+                    projection.push(index);
+                }
+            }
+            file.projection = Some(projection);
+        }
+    }
+    Ok(file)
+}
+
+/// Checks that the names of a weight or show declaration refer to variables. Names that occur in no
+/// constraint are variables as long as the header declares more variables than the constraints use.
+fn check_known_names(names: &[Pair<Rule>], file: &File) -> Result<(), ParseError> {
+    let mut unknown: Vec<&Pair<Rule>> = names.iter().filter(|n| file.name_map.get_by_left(n.as_str()).is_none()).collect();
+    unknown.sort_by_key(|n| n.as_str());
+    unknown.dedup_by_key(|n| n.as_str());
+    let Some(first) = unknown.first() else {
+        return Ok(());
+    };
+    let undeclared = (file.declared_variables.unwrap_or(0) as usize).saturating_sub(file.name_map.len());
+    if unknown.len() <= undeclared {
+        return Ok(());
+    }
+    Err(ParseError::from_pair(first, format!("unknown variable `{}`", first.as_str())))
+}
+
+fn parse_literal_weight(rule: Pair<Rule>, file: &mut File) -> Result<LiteralWeight, ParseError> {
+    let mut weight = LiteralWeight {
+        name: 0,
+        negated: false,
+        weight: BigRational::zero()
+    };
+    for inner_rule in rule.into_inner(){
+        match inner_rule.as_rule() {
+            Rule::negation => {
+                weight.negated = true;
+            }
+            Rule::var_name => {
+                weight.name = variable_index(inner_rule.as_str(), file);
+            }
+            Rule::weight_value => {
+                weight.weight = parse_weight(inner_rule.as_str())
+                    .ok_or_else(|| ParseError::from_pair(&inner_rule, format!("invalid weight `{}`", inner_rule.as_str())))?;
+            }
+            _ => unreachable!()
+        }
+    }
+    Ok(weight)
 }
 
-fn parse_equation(rule: Pair<Rule>, file: &mut File) -> Equation {
+/// Parses a weight written as an integer, a decimal number with optional exponent or a fraction.
+pub(crate) fn parse_weight(value: &str) -> Option<BigRational> {
+    if let Some((numerator, denominator)) = value.split_once('/') {
+        let numerator: BigInt = numerator.parse().ok()?;
+        let denominator: BigInt = denominator.parse().ok()?;
+        if denominator.is_zero() {
+            return None;
+        }
+        return Some(BigRational::new(numerator, denominator));
+    }
+
+    let (mantissa, exponent) = match value.split_once(['e', 'E']) {
+        Some((m, e)) => (m, e.parse::<i32>().ok()?),
+        None => (value, 0)
+    };
+    let (negative, unsigned) = match mantissa.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa))
+    };
+    let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    if (integer.is_empty() && fraction.is_empty()) || !integer.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let mut numerator: BigInt = format!("{}{}", integer, fraction).parse().ok()?;
+    if negative {
+        numerator = -numerator;
+    }
+    let scale = exponent - fraction.len() as i32;
+    let power = BigInt::from(10).pow(scale.unsigned_abs());
+    if scale >= 0 {
+        Some(BigRational::from_integer(numerator * power))
+    } else {
+        Some(BigRational::new(numerator, power))
+    }
+}
+
+fn parse_objective(rule: Pair<Rule>, file: &mut File) -> Result<Objective, ParseError> {
+    let mut objective = Objective {
+        sense: ObjectiveSense::Minimize,
+        side: EquationSide {
+            literals: Vec::new()
+        }
+    };
+    for inner_rule in rule.into_inner(){
+        match inner_rule.as_rule() {
+            Rule::objective_sense => {
+                if inner_rule.as_str() == "max:" {
+                    objective.sense = ObjectiveSense::Maximize;
+                }
+            }
+            Rule::equation_side => {
+                objective.side = parse_equation_side(inner_rule, file)?;
+            }
+            _ => unreachable!()
+        }
+    }
+    Ok(objective)
+}
+
+fn parse_equation(rule: Pair<Rule>, file: &mut File) -> Result<Equation, ParseError> {
     let mut equation_sides = Vec::new();
     let mut equation_symbol = EquationSymbol::eq;
+    let equation_rule = rule.clone();
     for inner_rule in rule.into_inner(){
-        let tmp = inner_rule.as_rule();
         match inner_rule.as_rule() {
             Rule::equation_side=> {
-                equation_sides.push(parse_equation_side(inner_rule, file));
+                equation_sides.push(parse_equation_side(inner_rule, file)?);
             }
             Rule::equation_symbol => {
                 match inner_rule.as_str() {
@@ -62,72 +322,93 @@ fn parse_equation(rule: Pair<Rule>, file: &mut File) -> Equation {
         }
     }
     if equation_sides.len() != 2 {
-        panic!()
+        Err(ParseError::from_pair(&equation_rule, format!("expected two equation sides, found {}", equation_sides.len())))
     }else{
-        Equation {
+        Ok(Equation {
             e2: equation_sides.pop().unwrap(),
             e1: equation_sides.pop().unwrap(),
             symbol: equation_symbol
-        }
+        })
     }
 }
 
-fn parse_equation_side(rule: Pair<Rule>, file: &mut File) -> EquationSide {
+fn parse_equation_side(rule: Pair<Rule>, file: &mut File) -> Result<EquationSide, ParseError> {
     let mut equation_side = EquationSide{
         literals: Vec::new()
     };
     for inner_rule in rule.into_inner(){
         match inner_rule.as_rule() {
             Rule::literal=> {
-                equation_side.literals.push(parse_literal(inner_rule, file));
+                equation_side.literals.push(parse_literal(inner_rule, file)?);
             }
             Rule::first_literal => {
-                equation_side.literals.push(parse_literal(inner_rule, file));
+                equation_side.literals.push(parse_literal(inner_rule, file)?);
             }
             Rule::implicit_one_literal => {
-                equation_side.literals.push(parse_literal(inner_rule, file));
+                equation_side.literals.push(parse_literal(inner_rule, file)?);
             }
             Rule::factor_value => {
-                equation_side.literals.push(EquationPart::Factor(inner_rule.as_str().parse().unwrap()));
+                equation_side.literals.push(EquationPart::Factor(parse_coefficient(&inner_rule)?));
             }
             Rule::factor => {
-                equation_side.literals.push(EquationPart::Factor(parse_factor(inner_rule)));
+                equation_side.literals.push(EquationPart::Factor(parse_factor(inner_rule)?));
             }
             _ => unreachable!()
         }
     }
-    equation_side
+    Ok(equation_side)
 }
 
-fn parse_literal(rule: Pair<Rule>, file: &mut File) -> EquationPart {
-    let mut factor: i32 = 1;
-    let mut name = "";
+fn parse_literal(rule: Pair<Rule>, file: &mut File) -> Result<EquationPart, ParseError> {
+    let mut factor: i64 = 1;
+    let mut negated = false;
+    let mut literals = Vec::new();
 
     for inner_rule in rule.into_inner(){
         match inner_rule.as_rule() {
             Rule::factor_sign => {
                 if inner_rule.as_str().eq("-") {
-                    factor = factor * -1;
+                    factor = -factor;
                 }
             }
             Rule::factor_value => {
-                let tmp = inner_rule.as_str().trim();
-                let tmp_value: i32 = inner_rule.as_str().trim().parse().unwrap();
-
-                factor = factor * tmp_value;
+                factor *= parse_coefficient(&inner_rule)?;
+            }
+            Rule::negation => {
+                negated = true;
             }
             Rule::var_name => {
-                name = inner_rule.as_str();
+                literals.push(ProductLiteral {
+                    name: variable_index(inner_rule.as_str(), file),
+                    negated
+                });
+                negated = false;
             }
             Rule::factor => {
-                factor = parse_factor(inner_rule);
+                factor = parse_factor(inner_rule)?;
             }
             _ => unreachable!()
         }
     }
 
+    if literals.len() == 1 {
+        let literal = literals.pop().unwrap();
+        Ok(EquationPart::Literal {
+            name: literal.name,
+            factor,
+            negated: literal.negated,
+        })
+    } else {
+        Ok(EquationPart::Product {
+            factor,
+            literals,
+        })
+    }
+}
+
+fn variable_index(name: &str, file: &mut File) -> u32 {
     let map_entry = file.name_map.get_by_left(&name.to_string());
-    let mut index: u32;
+    let index: u32;
     match map_entry {
         Some(i) => {
             index = *i;
@@ -135,31 +416,27 @@ fn parse_literal(rule: Pair<Rule>, file: &mut File) -> EquationPart {
         None => {
             index = file.name_index;
             file.name_map.insert(name.to_string(), index);
-            file.name_index = file.name_index + 1;
+            file.name_index += 1;
         }
     }
-    EquationPart::Literal {
-        name: index,
-        factor,
-    }
+    index
 }
 
-fn parse_factor(rule: Pair<Rule>) -> i32 {
-    let mut factor: i32 = 1;
+fn parse_factor(rule: Pair<Rule>) -> Result<i64, ParseError> {
+    let mut factor: i64 = 1;
 
     for inner_rule in rule.into_inner() {
         match inner_rule.as_rule() {
             Rule::factor_sign => {
                 if inner_rule.as_str().eq("-") {
-                    factor = factor * -1;
+                    factor = -factor;
                 }
             }
             Rule::factor_value => {
-                let tmp_value: i32 = inner_rule.as_str().trim().parse().unwrap();
-                factor = factor * tmp_value;
+                factor *= parse_coefficient(&inner_rule)?;
             }
             _ => unreachable!()
         }
     }
-    factor
+    Ok(factor)
 }
@@ -1,10 +1,52 @@
-use std::collections::HashMap;
-use crate::parsing::equation_ds::{Equation, EquationPart, EquationSymbol, File};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use crate::parsing::equation_ds::{Equation, EquationPart, EquationSide, EquationSymbol, File, LiteralWeight, ProductLiteral, Substitution};
 use crate::parsing::equation_ds::EquationPart::{Factor, Literal};
 
-pub fn preprocess_file(mut file: File) -> File{
+/// Number of constraints the passes of [`preprocess_file_with_report`] removed. Every pass only removes
+/// constraints that are implied by the remaining ones, so the set of models stays the same.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PreprocessingReport {
+    /// Constraints that hold under every assignment.
+    pub satisfied: usize,
+    /// Constraints equal to an earlier one.
+    pub duplicates: usize,
+    /// Constraints over the same literals as a stronger constraint, with smaller or equal degree and larger or equal coefficients.
+    pub subsumed: usize,
+    /// Whether a constraint can never hold, the constraints are then replaced by `0 >= 1`.
+    pub unsatisfiable: bool,
+    /// Variables with the same value in every model, each of them keeps one unit constraint.
+    pub fixed: usize,
+    /// Variables replaced by an equivalent or complementary literal, see [`File::substitutions`].
+    pub substituted: usize,
+}
+
+impl Display for PreprocessingReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} satisfied, {} duplicate, {} subsumed constraints removed", self.satisfied, self.duplicates, self.subsumed)?;
+        write!(f, ", {} fixed and {} substituted variables", self.fixed, self.substituted)?;
+        if self.unsatisfiable {
+            write!(f, ", unsatisfiable")?;
+        }
+        Ok(())
+    }
+}
+
+pub fn preprocess_file(file: File) -> File{
+    preprocess_file_with_report(file).0
+}
+
+/// Like [`preprocess_file`], additionally returns what the removal passes did.
+pub fn preprocess_file_with_report(mut file: File) -> (File, PreprocessingReport){
     let mut new_equation_list = Vec::new();
 
+    // substitutions of an earlier run are found again
+    let mut substitutions = file.substitution_equations();
+    file.equations.append(&mut substitutions);
+    file.substitutions.clear();
+
+    preprocessing_linearize_products(&mut file);
+
     for mut equation in file.equations {
         preprocessing_literals_left_numbers_right(&mut equation);
         preprocessing_add_up_same(&mut equation);
@@ -27,11 +69,361 @@ pub fn preprocess_file(mut file: File) -> File{
     }
     file.equations = new_equation_list;
     preprocessing_all_ge(&mut file);
-    file
+    for equation in &mut file.equations {
+        preprocessing_normalize(equation);
+    }
+
+    // the passes can remove every occurrence of a variable, the header keeps it in the written file
+    file.declared_variables = Some(file.variable_count());
+    let mut report = PreprocessingReport::default();
+    preprocessing_eliminate(&mut file, &mut report);
+    report.satisfied = preprocessing_remove_satisfied(&mut file);
+    if preprocessing_find_unsatisfiable(&mut file) {
+        report.unsatisfiable = true;
+        return (file, report);
+    }
+    report.duplicates = preprocessing_remove_duplicates(&mut file);
+    report.subsumed = preprocessing_remove_subsumed(&mut file);
+    (file, report)
+}
+
+/// Union-find over the variables, every variable equals its parent or, if its parity is set, the negation of it.
+struct Equivalences {
+    parent: Vec<u32>,
+    parity: Vec<bool>,
+}
+
+impl Equivalences {
+    fn new(variables: u32) -> Equivalences {
+        Equivalences {
+            parent: (0..variables).collect(),
+            parity: vec![false; variables as usize],
+        }
+    }
+
+    /// Representative of the variable and whether the variable is its negation.
+    fn find(&mut self, variable: u32) -> (u32, bool) {
+        let mut path = Vec::new();
+        let mut root = variable;
+        while self.parent[root as usize] != root {
+            path.push(root);
+            root = self.parent[root as usize];
+        }
+        let mut parity = false;
+        for v in path.iter().rev() {
+            parity ^= self.parity[*v as usize];
+            self.parent[*v as usize] = root;
+            self.parity[*v as usize] = parity;
+        }
+        (root, parity)
+    }
+
+    /// Records `a = b xor parity`. Returns whether the classes were merged, or `None` if the
+    /// record contradicts the earlier ones.
+    fn union(&mut self, a: u32, b: u32, parity: bool) -> Option<bool> {
+        let (ra, pa) = self.find(a);
+        let (rb, pb) = self.find(b);
+        if ra == rb {
+            return if pa ^ pb == parity { Some(false) } else { None };
+        }
+        // the smaller variable represents the class
+        let (child, root) = if ra > rb { (ra, rb) } else { (rb, ra) };
+        self.parent[child as usize] = root;
+        self.parity[child as usize] = pa ^ pb ^ parity;
+        Some(true)
+    }
+}
+
+/// Substitutes fixed variables and equivalent literals until neither is left. Fixed variables keep a
+/// unit constraint, so unit propagation weighs them in every count. Substituted variables are recorded
+/// in [`File::substitutions`] and their literal weights are multiplied into the weights of their
+/// representative. Variables are only substituted by variables with the same membership in the
+/// projection, so projected counts stay exact as well.
+fn preprocessing_eliminate(file: &mut File, report: &mut PreprocessingReport) {
+    let projection: Option<HashSet<u32>> = file.projection.as_ref().map(|p| p.iter().copied().collect());
+    let mut equivalences = Equivalences::new(file.variable_count());
+    let mut fixed: BTreeMap<u32, bool> = BTreeMap::new();
+    loop {
+        let units = match find_units(&file.equations) {
+            Some(units) => units,
+            None => {
+                file.equations.push(unsatisfiable_equation());
+                return;
+            }
+        };
+        let changed = if !units.is_empty() {
+            fixed.extend(units);
+            true
+        } else {
+            let mut merged = false;
+            for (a, b, parity) in find_equivalent_pairs(&file.equations) {
+                if projection.as_ref().is_some_and(|p| p.contains(&a) != p.contains(&b)) {
+                    continue;
+                }
+                match equivalences.union(a, b, parity) {
+                    Some(m) => merged |= m,
+                    None => {
+                        file.equations.push(unsatisfiable_equation());
+                        return;
+                    }
+                }
+            }
+            merged
+        };
+        if !changed {
+            break;
+        }
+        for equation in &mut file.equations {
+            substitute(equation, &fixed, &mut equivalences);
+        }
+    }
+
+    for v in 0..file.variable_count() {
+        let (root, parity) = equivalences.find(v);
+        if root != v {
+            file.substitutions.push(Substitution { name: v, literal: root, negated: parity });
+        }
+    }
+    if !file.substitutions.is_empty() {
+        let mut weights: BTreeMap<(u32, bool), LiteralWeight> = BTreeMap::new();
+        for mut w in std::mem::take(&mut file.weights) {
+            let (root, parity) = equivalences.find(w.name);
+            w.name = root;
+            w.negated ^= parity;
+            match weights.get_mut(&(w.name, w.negated)) {
+                Some(existing) => existing.weight *= w.weight,
+                None => {
+                    weights.insert((w.name, w.negated), w);
+                }
+            }
+        }
+        file.weights = weights.into_values().collect();
+    }
+    for (name, value) in &fixed {
+        file.equations.push(Equation{
+            symbol: EquationSymbol::ge,
+            e1: EquationSide{ literals: vec![Literal { factor: 1, name: *name, negated: !*value }] },
+            e2: EquationSide{ literals: vec![Factor(1)] }
+        });
+    }
+    report.fixed = fixed.len();
+    report.substituted = file.substitutions.len();
+}
+
+/// Variables that have the same value in every model because a literal of them has a coefficient
+/// larger than the slack of its constraint. Returns `None` if a constraint can never hold.
+fn find_units(equations: &[Equation]) -> Option<BTreeMap<u32, bool>> {
+    let mut units = BTreeMap::new();
+    for equation in equations {
+        let (literals, degree) = normalized_parts(equation);
+        if degree <= 0 {
+            continue;
+        }
+        let slack = literals.iter().map(|(_, _, f)| f).sum::<i64>() - degree;
+        if slack < 0 {
+            return None;
+        }
+        for (name, negated, factor) in literals {
+            if factor > slack && units.insert(name, !negated) == Some(negated) {
+                return None;
+            }
+        }
+    }
+    Some(units)
+}
+
+/// Pairs `(a, b, parity)` with `a = b xor parity`, taken from clauses `l1 + l2 >= 1` whose negation
+/// `~l1 + ~l2 >= 1` is a constraint as well. Normalized binary clauses have coefficients and degree 1.
+fn find_equivalent_pairs(equations: &[Equation]) -> Vec<(u32, u32, bool)> {
+    let mut clauses = HashSet::new();
+    for equation in equations {
+        let (literals, degree) = normalized_parts(equation);
+        if literals.len() == 2 && degree == 1 && literals.iter().all(|(_, _, f)| *f == 1) {
+            clauses.insert(((literals[0].0, literals[0].1), (literals[1].0, literals[1].1)));
+        }
+    }
+    // every pair is found from both clauses, the one whose first literal is positive reports it
+    clauses.iter()
+        .filter(|((a, na), (b, nb))| !*na && clauses.contains(&((*a, true), (*b, !*nb))))
+        .map(|((a, na), (b, nb))| (*a, *b, !(*na ^ *nb)))
+        .collect()
+}
+
+/// Replaces fixed variables by their value and variables by the literal of their representative, then normalizes again.
+fn substitute(equation: &mut Equation, fixed: &BTreeMap<u32, bool>, equivalences: &mut Equivalences) {
+    let mut changed = false;
+    for part in &mut equation.e1.literals {
+        if let Literal { factor, name, negated } = part {
+            if let Some(value) = fixed.get(name) {
+                *part = Factor(if *value != *negated { *factor } else { 0 });
+                changed = true;
+            } else {
+                let (root, parity) = equivalences.find(*name);
+                if root != *name {
+                    *name = root;
+                    *negated ^= parity;
+                    changed = true;
+                }
+            }
+        }
+    }
+    if changed {
+        preprocessing_literals_left_numbers_right(equation);
+        preprocessing_add_up_same(equation);
+        preprocessing_normalize(equation);
+    }
+}
+
+fn unsatisfiable_equation() -> Equation {
+    Equation{
+        symbol: EquationSymbol::ge,
+        e1: EquationSide{ literals: vec![Factor(0)] },
+        e2: EquationSide{ literals: vec![Factor(1)] }
+    }
+}
+
+/// Literals as `(name, negated, factor)` and degree of a normalized constraint.
+type NormalizedParts = (Vec<(u32, bool, i64)>, i64);
+
+fn normalized_parts(equation: &Equation) -> NormalizedParts {
+    let literals = equation.e1.literals.iter().filter_map(|l| match l {
+        EquationPart::Literal { factor, name, negated } => Some((*name, *negated, *factor)),
+        _ => None
+    }).collect();
+    let degree = match equation.e2.literals.first() {
+        Some(Factor(f)) => *f,
+        _ => 0
+    };
+    (literals, degree)
+}
+
+/// A normalized constraint with a degree of at most 0 holds because all coefficients are positive.
+fn preprocessing_remove_satisfied(file: &mut File) -> usize {
+    let before = file.equations.len();
+    file.equations.retain(|e| normalized_parts(e).1 > 0);
+    before - file.equations.len()
+}
+
+/// If the coefficients of a constraint sum up to less than its degree the formula has no model,
+/// all constraints are replaced by the single constraint `0 >= 1`.
+fn preprocessing_find_unsatisfiable(file: &mut File) -> bool {
+    let unsatisfiable = file.equations.iter().any(|e| {
+        let (literals, degree) = normalized_parts(e);
+        literals.iter().map(|(_, _, f)| f).sum::<i64>() < degree
+    });
+    if unsatisfiable {
+        file.equations = vec![unsatisfiable_equation()];
+    }
+    unsatisfiable
+}
+
+/// Normalized constraints have sorted literals, so equal constraints have equal parts.
+fn preprocessing_remove_duplicates(file: &mut File) -> usize {
+    let before = file.equations.len();
+    let mut seen = HashSet::new();
+    file.equations.retain(|e| seen.insert(normalized_parts(e)));
+    before - file.equations.len()
+}
+
+/// Removes a constraint `sum b*l >= d2` if a constraint `sum a*l >= d1` over the same literals has
+/// `a <= b` for every literal and `d1 >= d2`, because then `sum b*l >= sum a*l >= d1 >= d2`.
+fn preprocessing_remove_subsumed(file: &mut File) -> usize {
+    let parts: Vec<NormalizedParts> = file.equations.iter().map(normalized_parts).collect();
+    let mut groups: HashMap<Vec<(u32, bool)>, Vec<usize>> = HashMap::new();
+    for (i, (literals, _)) in parts.iter().enumerate() {
+        groups.entry(literals.iter().map(|(n, neg, _)| (*n, *neg)).collect()).or_default().push(i);
+    }
+    let mut removed = vec![false; parts.len()];
+    for group in groups.values() {
+        for j in group {
+            let (weaker, d2) = &parts[*j];
+            removed[*j] = group.iter().any(|i| {
+                let (stronger, d1) = &parts[*i];
+                i != j && !removed[*i] && d1 >= d2 && stronger.iter().zip(weaker).all(|(a, b)| a.2 <= b.2)
+            });
+        }
+    }
+    let mut index = 0;
+    file.equations.retain(|_| {
+        index += 1;
+        !removed[index - 1]
+    });
+    removed.iter().filter(|r| **r).count()
+}
+
+/// Replaces every product of literals by an auxiliary variable that is equivalent to the product,
+/// the model count is unchanged because the auxiliary variable is a function of the other variables.
+fn preprocessing_linearize_products(file: &mut File){
+    let mut auxiliary: HashMap<Vec<ProductLiteral>, u32> = HashMap::new();
+    let mut definitions = Vec::new();
+    let mut equations = std::mem::take(&mut file.equations);
+
+    for equation in &mut equations {
+        for side in [&mut equation.e1, &mut equation.e2] {
+            for part in &mut side.literals {
+                if let EquationPart::Product { factor, literals } = part {
+                    let literals = literals.clone();
+                    *part = linearize_product(*factor, literals, file, &mut auxiliary, &mut definitions);
+                }
+            }
+        }
+    }
+    equations.append(&mut definitions);
+    file.equations = equations;
+}
+
+fn linearize_product(factor: i64, mut literals: Vec<ProductLiteral>, file: &mut File, auxiliary: &mut HashMap<Vec<ProductLiteral>, u32>, definitions: &mut Vec<Equation>) -> EquationPart {
+    literals.sort();
+    literals.dedup();
+    // x and ~x are next to each other after sorting, such a product is always 0
+    if literals.windows(2).any(|w| w[0].name == w[1].name) {
+        return Factor(0);
+    }
+    if literals.len() == 1 {
+        return Literal {
+            factor,
+            name: literals[0].name,
+            negated: literals[0].negated
+        };
+    }
+
+    let name = match auxiliary.get(&literals) {
+        Some(name) => *name,
+        None => {
+            let name = file.fresh_variable("aux");
+            // name implies every literal: l1 + ... + lk - k*name >= 0
+            let mut implies_literals = EquationSide{
+                literals: literals.iter().map(|l| Literal { factor: 1, name: l.name, negated: l.negated }).collect()
+            };
+            implies_literals.literals.push(Literal { factor: -(literals.len() as i64), name, negated: false });
+            definitions.push(Equation{
+                symbol: EquationSymbol::ge,
+                e1: implies_literals,
+                e2: EquationSide{ literals: vec![Factor(0)] }
+            });
+            // the literals together imply name: name + ~l1 + ... + ~lk >= 1
+            let mut implied_by_literals = EquationSide{
+                literals: literals.iter().map(|l| Literal { factor: 1, name: l.name, negated: !l.negated }).collect()
+            };
+            implied_by_literals.literals.push(Literal { factor: 1, name, negated: false });
+            definitions.push(Equation{
+                symbol: EquationSymbol::ge,
+                e1: implied_by_literals,
+                e2: EquationSide{ literals: vec![Factor(1)] }
+            });
+            auxiliary.insert(literals, name);
+            name
+        }
+    };
+    Literal {
+        factor,
+        name,
+        negated: false
+    }
 }
 
 fn preprocessing_all_ge(file: &mut File){
-    for mut eq in &mut file.equations {
+    for eq in &mut file.equations {
         if eq.symbol == EquationSymbol::le {
             for l in &mut eq.e1.literals {
                 match l {
@@ -41,6 +433,7 @@ fn preprocessing_all_ge(file: &mut File){
                     EquationPart::Factor(f) => {
                         *f *= -1;
                     }
+                    EquationPart::Product { .. } => unreachable!("products are linearized first"),
                 }
             }
             for l in &mut eq.e2.literals {
@@ -51,6 +444,7 @@ fn preprocessing_all_ge(file: &mut File){
                     EquationPart::Factor(f) => {
                         *f *= -1;
                     }
+                    EquationPart::Product { .. } => unreachable!("products are linearized first"),
                 }
             }
             eq.symbol = EquationSymbol::ge;
@@ -58,38 +452,114 @@ fn preprocessing_all_ge(file: &mut File){
     }
 }
 
+/// Brings a `>=` constraint into a canonical form: negative coefficients are replaced by negated
+/// literals, `a*x = a + (-a)*~x`, coefficients larger than the degree are lowered to the degree and
+/// all coefficients and the degree are divided by their greatest common divisor, rounding the degree up.
+/// Constraints that hold under every assignment are only rewritten to positive coefficients.
+fn preprocessing_normalize(equation: &mut Equation){
+    let mut degree = match equation.e2.literals.first() {
+        Some(Factor(f)) => *f,
+        _ => 0
+    };
+    for l in &mut equation.e1.literals {
+        if let EquationPart::Literal { factor, negated, .. } = l {
+            if *factor < 0 {
+                *factor = -*factor;
+                *negated = !*negated;
+                degree += *factor;
+            }
+        }
+    }
+    equation.e1.literals.sort_by_key(|l| match l {
+        EquationPart::Literal { name, .. } => *name,
+        _ => u32::MAX
+    });
+
+    if degree > 0 {
+        let mut divisor = 0;
+        for l in &mut equation.e1.literals {
+            if let EquationPart::Literal { factor, .. } = l {
+                *factor = (*factor).min(degree);
+                divisor = gcd(divisor, *factor);
+            }
+        }
+        if divisor > 1 {
+            for l in &mut equation.e1.literals {
+                if let EquationPart::Literal { factor, .. } = l {
+                    *factor /= divisor;
+                }
+            }
+            degree = (degree + divisor - 1) / divisor;
+        }
+    }
+    equation.e2.literals = vec![Factor(degree)];
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
 fn preprocessing_literals_left_numbers_right(equation: &mut Equation){
     let mut lit_vec = Vec::new();
     let mut num_vec = Vec::new();
 
+    // a negated literal ~x is replaced by 1 - x
     for item in &equation.e1.literals {
         match item {
             EquationPart::Literal{
                 factor,
-                name
+                name,
+                negated: false
             } => {
                 lit_vec.push(Literal {
                     factor: *factor,
-                    name: *name})
+                    name: *name,
+                    negated: false})
+            },
+            EquationPart::Literal{
+                factor,
+                name,
+                negated: true
+            } => {
+                lit_vec.push(Literal {
+                    factor: -*factor,
+                    name: *name,
+                    negated: false});
+                num_vec.push(Factor(-*factor))
             },
             EquationPart::Factor(i) => {
                 num_vec.push(Factor(*i * -1))
             }
+            EquationPart::Product { .. } => unreachable!("products are linearized first"),
         }
     }
     for item in &equation.e2.literals {
         match item {
             EquationPart::Literal{
                 factor,
-                name
+                name,
+                negated: false
             } => {
                 lit_vec.push(Literal {
-                    factor: *factor * -1,
-                    name: *name})
+                    factor: -*factor,
+                    name: *name,
+                    negated: false})
+            },
+            EquationPart::Literal{
+                factor,
+                name,
+                negated: true
+            } => {
+                lit_vec.push(Literal {
+                    factor: *factor,
+                    name: *name,
+                    negated: false});
+                num_vec.push(Factor(*factor))
             },
             EquationPart::Factor(i) => {
                 num_vec.push(Factor(*i))
             }
+            EquationPart::Product { .. } => unreachable!("products are linearized first"),
         }
     }
 
@@ -98,15 +568,16 @@ fn preprocessing_literals_left_numbers_right(equation: &mut Equation){
 }
 
 fn preprocessing_add_up_same(equation: &mut Equation){
-    let mut hash_map: HashMap<u32, i32> = HashMap::new();
+    let mut hash_map: HashMap<u32, i64> = HashMap::new();
     let mut factors = 0;
     for l in &equation.e1.literals {
         match l {
             EquationPart::Factor(f) => {
                 factors += f;
             }
-            EquationPart::Literal {factor, name} => {
-                let res = hash_map.get(&name);
+            EquationPart::Product { .. } => unreachable!("products are linearized first"),
+            EquationPart::Literal {factor, name, ..} => {
+                let res = hash_map.get(name);
                 match res {
                     None => {
                         hash_map.insert(*name, *factor);
@@ -123,8 +594,9 @@ fn preprocessing_add_up_same(equation: &mut Equation){
             EquationPart::Factor(f) => {
                 factors += f;
             }
-            EquationPart::Literal {factor, name} => {
-                let res = hash_map.get(&name);
+            EquationPart::Product { .. } => unreachable!("products are linearized first"),
+            EquationPart::Literal {factor, name, ..} => {
+                let res = hash_map.get(name);
                 match res {
                     None => {
                         hash_map.insert(*name, *factor);
@@ -144,7 +616,8 @@ fn preprocessing_add_up_same(equation: &mut Equation){
         }
         output.push(EquationPart::Literal {
             name: i,
-            factor: f
+            factor: f,
+            negated: false
         });
     }
     equation.e1.literals = output;
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::mem::size_of;
+use std::sync::Mutex;
+use crate::solving::weights::HeapSize;
+
+/// Counters of a [`Cache`], reported at the end of a run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// Entries dropped by [`Cache::invalidate_since`].
+    pub invalidated: u64,
+    /// Number of entries currently stored.
+    pub entries: usize,
+    /// Estimated memory used by the stored entries in bytes, including the heap memory of their values.
+    pub memory: usize,
+}
+
+impl Display for CacheStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} hits, {} misses, {} evictions, {} invalidated, {} entries, {} bytes", self.hits, self.misses, self.evictions, self.invalidated, self.entries, self.memory)
+    }
+}
+
+struct CacheEntry<V> {
+    value: V,
+    last_used: u64,
+    inserted: u64,
+    /// Estimated memory of the entry, computed once on insertion.
+    size: usize,
+}
+
+/// Cache of subformula counts keyed on [`crate::solving::pb_ds::Residual::cache_key`].
+///
+/// The memory of the entries is estimated from the key length, the size of the entry itself and the
+/// [`HeapSize`] of the value. Once the estimate exceeds the memory limit the least recently
+/// used entries are evicted until a quarter of the limit is free again, which keeps the eviction cost
+/// amortized constant per insert.
+///
+/// Entries can be invalidated by the time they were inserted, see [`Cache::invalidate_since`]. The
+/// invalidated time intervals are kept until the next eviction and checked when an entry is read.
+pub struct Cache<V> {
+    entries: HashMap<Box<[i64]>, CacheEntry<V>>,
+    memory_limit: usize,
+    time: u64,
+    /// Sorted disjoint intervals of insertion times whose entries are invalid.
+    invalid: Vec<(u64, u64)>,
+    stats: CacheStats,
+}
+
+impl<V: Clone + HeapSize> Cache<V> {
+    pub fn new(memory_limit: usize) -> Cache<V> {
+        Cache {
+            entries: HashMap::new(),
+            memory_limit,
+            time: 0,
+            invalid: Vec::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn get(&mut self, key: &[i64]) -> Option<V> {
+        self.time += 1;
+        if self.entries.get(key).is_some_and(|e| contains(&self.invalid, e.inserted)) {
+            let entry = self.entries.remove(key).unwrap();
+            self.stats.invalidated += 1;
+            self.stats.entries -= 1;
+            self.stats.memory -= entry.size;
+        }
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                entry.last_used = self.time;
+                self.stats.hits += 1;
+                Some(entry.value.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: Box<[i64]>, value: V) {
+        self.time += 1;
+        let size = entry_size(&key, &value);
+        let entry = CacheEntry {
+            value,
+            last_used: self.time,
+            inserted: self.time,
+            size,
+        };
+        self.stats.memory += size;
+        match self.entries.insert(key, entry) {
+            Some(old) => self.stats.memory -= old.size,
+            None => self.stats.entries += 1,
+        }
+        if self.stats.memory > self.memory_limit {
+            self.evict();
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Current time of the cache, it advances with every access.
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// Invalidates all entries inserted after `time`.
+    pub fn invalidate_since(&mut self, time: u64) {
+        let start = time + 1;
+        if start > self.time {
+            return;
+        }
+        // earlier intervals end before the current time, so the new one covers all that start later
+        while self.invalid.last().is_some_and(|(s, _)| *s >= start) {
+            self.invalid.pop();
+        }
+        match self.invalid.last_mut() {
+            Some((_, end)) if *end + 1 >= start => *end = self.time,
+            _ => self.invalid.push((start, self.time)),
+        }
+    }
+
+    /// Removes the invalid entries and then the least recently used entries until the memory
+    /// estimate is at most three quarters of the limit.
+    fn evict(&mut self) {
+        if !self.invalid.is_empty() {
+            let invalid = std::mem::take(&mut self.invalid);
+            let before = self.entries.len();
+            let mut memory = self.stats.memory;
+            self.entries.retain(|_, e| {
+                let invalid = contains(&invalid, e.inserted);
+                if invalid {
+                    memory -= e.size;
+                }
+                !invalid
+            });
+            self.stats.invalidated += (before - self.entries.len()) as u64;
+            self.stats.entries = self.entries.len();
+            self.stats.memory = memory;
+        }
+        let target = self.memory_limit / 4 * 3;
+        let mut usage: Vec<(u64, usize)> = self.entries.values().map(|e| (e.last_used, e.size)).collect();
+        usage.sort_unstable();
+        let mut memory = self.stats.memory;
+        let mut cutoff = 0;
+        for (last_used, size) in usage {
+            if memory <= target {
+                break;
+            }
+            memory -= size;
+            cutoff = last_used;
+        }
+        let before = self.entries.len();
+        self.entries.retain(|_, e| e.last_used > cutoff);
+        self.stats.evictions += (before - self.entries.len()) as u64;
+        self.stats.entries = self.entries.len();
+        self.stats.memory = memory;
+    }
+}
+
+/// [`Cache`] shared between threads, split into shards that are locked independently.
+/// Every shard gets an equal part of the memory limit.
+pub struct ConcurrentCache<V> {
+    shards: Vec<Mutex<Cache<V>>>,
+}
+
+impl<V: Clone + HeapSize> ConcurrentCache<V> {
+    pub fn new(memory_limit: usize, shards: usize) -> ConcurrentCache<V> {
+        let shards = shards.max(1);
+        ConcurrentCache {
+            shards: (0..shards).map(|_| Mutex::new(Cache::new(memory_limit / shards))).collect(),
+        }
+    }
+
+    fn shard(&self, key: &[i64]) -> &Mutex<Cache<V>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+
+    pub fn get(&self, key: &[i64]) -> Option<V> {
+        self.shard(key).lock().unwrap().get(key)
+    }
+
+    pub fn insert(&self, key: Box<[i64]>, value: V) {
+        self.shard(&key).lock().unwrap().insert(key, value)
+    }
+
+    /// Sum of the statistics of all shards.
+    pub fn stats(&self) -> CacheStats {
+        let mut stats = CacheStats::default();
+        for shard in &self.shards {
+            let s = shard.lock().unwrap().stats();
+            stats.hits += s.hits;
+            stats.misses += s.misses;
+            stats.evictions += s.evictions;
+            stats.invalidated += s.invalidated;
+            stats.entries += s.entries;
+            stats.memory += s.memory;
+        }
+        stats
+    }
+}
+
+fn entry_size<V: HeapSize>(key: &[i64], value: &V) -> usize {
+    size_of::<Box<[i64]>>() + size_of::<CacheEntry<V>>() + size_of_val(key) + value.heap_size()
+}
+
+/// Whether one of the sorted disjoint intervals contains `time`.
+fn contains(intervals: &[(u64, u64)], time: u64) -> bool {
+    let i = intervals.partition_point(|(start, _)| *start <= time);
+    i > 0 && time <= intervals[i - 1].1
+}
@@ -0,0 +1,401 @@
+//! Knowledge compilation into decision-DNNF.
+//!
+//! The search of [`crate::solving::solver::Solver`] is a decision-DNNF: the two branches of a
+//! decision are the children of an OR node, the components of a subformula and the literals
+//! assigned with a decision the children of an AND node. Counting with [`Compiler`] as weights
+//! records this trace, cached subformulas return the same [`Nnf`], so the result is a DAG.
+//!
+//! The circuit only contains the counted variables, with a projection the variables outside of it
+//! are forgotten. Every OR node mentions the same variables in both children, so the circuit is smooth.
+//! The auxiliary variables of linearized products are part of the circuit, their values follow from
+//! the other variables.
+//!
+//! A [`Ddnnf`] answers counting queries in time linear in its size: counts under assumptions, the
+//! counts of every literal in one pass and weighted marginals.
+
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::{Add, Mul};
+use std::sync::Arc;
+use num_bigint::BigUint;
+use num_rational::BigRational;
+use num_traits::{One, Zero};
+use crate::parsing::equation_ds::Substitution;
+use crate::solving::weights::{HeapSize, LiteralWeights, Unweighted, Weights};
+
+enum Gate {
+    True,
+    False,
+    Literal(u32, bool),
+    And(Vec<Nnf>),
+    Or(Vec<Nnf>),
+}
+
+/// Circuit built while counting, AND and OR of circuits are `*` and `+`. Constants are simplified away.
+#[derive(Clone)]
+pub struct Nnf(Arc<Gate>);
+
+impl Nnf {
+    pub fn literal(variable: u32, value: bool) -> Nnf {
+        Nnf(Arc::new(Gate::Literal(variable, value)))
+    }
+
+    /// Children of an AND (or OR) gate no one else refers to, they can be merged into a parent of the same kind.
+    fn take_children(self, and: bool) -> Result<Vec<Nnf>, Nnf> {
+        if !matches!((and, &*self.0), (true, Gate::And(_)) | (false, Gate::Or(_))) {
+            return Err(self);
+        }
+        match Arc::try_unwrap(self.0) {
+            Ok(Gate::And(children)) | Ok(Gate::Or(children)) => Ok(children),
+            Ok(gate) => Err(Nnf(Arc::new(gate))),
+            Err(gate) => Err(Nnf(gate)),
+        }
+    }
+
+    fn combine(self, other: Nnf, and: bool) -> Nnf {
+        let mut children = Vec::new();
+        for nnf in [self, other] {
+            match nnf.take_children(and) {
+                Ok(mut c) => children.append(&mut c),
+                Err(nnf) => children.push(nnf),
+            }
+        }
+        Nnf(Arc::new(if and { Gate::And(children) } else { Gate::Or(children) }))
+    }
+}
+
+impl Zero for Nnf {
+    fn zero() -> Self {
+        Nnf(Arc::new(Gate::False))
+    }
+
+    fn is_zero(&self) -> bool {
+        matches!(*self.0, Gate::False)
+    }
+}
+
+impl One for Nnf {
+    fn one() -> Self {
+        Nnf(Arc::new(Gate::True))
+    }
+}
+
+impl Add for Nnf {
+    type Output = Nnf;
+
+    fn add(self, other: Nnf) -> Nnf {
+        if self.is_zero() {
+            other
+        } else if other.is_zero() {
+            self
+        } else {
+            self.combine(other, false)
+        }
+    }
+}
+
+impl Mul for Nnf {
+    type Output = Nnf;
+
+    fn mul(self, other: Nnf) -> Nnf {
+        if self.is_zero() || matches!(*other.0, Gate::True) {
+            self
+        } else if other.is_zero() || matches!(*self.0, Gate::True) {
+            other
+        } else {
+            self.combine(other, true)
+        }
+    }
+}
+
+/// Counts the gate and the gates only it refers to, gates shared with other circuits are counted by
+/// the cache entries that hold them.
+impl HeapSize for Nnf {
+    fn heap_size(&self) -> usize {
+        let mut size = 0;
+        let mut stack = vec![self];
+        while let Some(nnf) = stack.pop() {
+            // the counters of the Arc are allocated next to the gate
+            size += 2 * size_of::<usize>() + size_of::<Gate>();
+            if let Gate::And(children) | Gate::Or(children) = &*nnf.0 {
+                size += children.capacity() * size_of::<Nnf>();
+                stack.extend(children.iter().filter(|c| Arc::strong_count(&c.0) == 1));
+            }
+        }
+        size
+    }
+}
+
+impl Display for Nnf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Ddnnf::new(self, 0, &[]))
+    }
+}
+
+impl Debug for Nnf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Nnf({} nodes)", Ddnnf::new(self, 0, &[]).nodes.len())
+    }
+}
+
+/// Weights whose count of a formula is its compiled circuit.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Compiler;
+
+impl Weights for Compiler {
+    type Value = Nnf;
+
+    fn literal(&self, variable: u32, value: bool) -> Nnf {
+        Nnf::literal(variable, value)
+    }
+
+    fn free(&self, variables: &[u32]) -> Nnf {
+        let children = variables.iter().map(|v| Nnf::literal(*v, true) + Nnf::literal(*v, false)).collect();
+        match variables.len() {
+            0 => Nnf::one(),
+            _ => Nnf(Arc::new(Gate::And(children))),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    True,
+    False,
+    Literal(u32, bool),
+    And(Vec<usize>),
+    /// Children and the variable the children disagree on, if known.
+    Or(Option<u32>, Vec<usize>),
+}
+
+/// Compiled circuit as a list of nodes, every node only refers to nodes before it and the root is the last one.
+#[derive(Clone, Debug)]
+pub struct Ddnnf {
+    pub nodes: Vec<Node>,
+    pub root: usize,
+    /// Number of variables of the formula, the largest variable of the circuit is smaller.
+    pub variables: u32,
+}
+
+impl Ddnnf {
+    /// Numbers the distinct gates of `nnf`. Every literal of a variable that other variables were
+    /// substituted with during preprocessing is extended by the literals of these variables.
+    pub fn new(nnf: &Nnf, variables: u32, substitutions: &[Substitution]) -> Ddnnf {
+        let mut substituted: HashMap<u32, Vec<&Substitution>> = HashMap::new();
+        for s in substitutions {
+            substituted.entry(s.literal).or_default().push(s);
+        }
+        let mut ddnnf = Ddnnf {
+            nodes: Vec::new(),
+            root: 0,
+            variables,
+        };
+        let mut index: HashMap<*const Gate, usize> = HashMap::new();
+        let mut literals: HashMap<(u32, bool), usize> = HashMap::new();
+        let mut stack: Vec<(&Nnf, bool)> = vec![(nnf, false)];
+        // children are pushed above their gate and numbered first
+        while let Some((nnf, expanded)) = stack.pop() {
+            let key = Arc::as_ptr(&nnf.0);
+            if index.contains_key(&key) {
+                continue;
+            }
+            let id = match &*nnf.0 {
+                Gate::True => ddnnf.push(Node::True),
+                Gate::False => ddnnf.push(Node::False),
+                Gate::Literal(v, b) => ddnnf.literal(&mut literals, &substituted, *v, *b),
+                Gate::And(children) | Gate::Or(children) if !expanded => {
+                    stack.push((nnf, true));
+                    stack.extend(children.iter().rev().map(|c| (c, false)));
+                    continue;
+                }
+                Gate::And(children) => {
+                    let children = children.iter().map(|c| index[&Arc::as_ptr(&c.0)]).collect();
+                    ddnnf.push(Node::And(children))
+                }
+                Gate::Or(children) => {
+                    let children: Vec<usize> = children.iter().map(|c| index[&Arc::as_ptr(&c.0)]).collect();
+                    let decision = ddnnf.decision(&children);
+                    ddnnf.push(Node::Or(decision, children))
+                }
+            };
+            index.insert(key, id);
+        }
+        ddnnf.root = index[&Arc::as_ptr(&nnf.0)];
+        ddnnf.variables = ddnnf.variables.max(ddnnf.nodes.iter().filter_map(|n| match n {
+            Node::Literal(v, _) => Some(*v + 1),
+            _ => None,
+        }).max().unwrap_or(0));
+        ddnnf
+    }
+
+    fn push(&mut self, node: Node) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    fn literal(&mut self, literals: &mut HashMap<(u32, bool), usize>, substituted: &HashMap<u32, Vec<&Substitution>>, variable: u32, value: bool) -> usize {
+        if let Some(id) = literals.get(&(variable, value)) {
+            return *id;
+        }
+        let mut id = self.push(Node::Literal(variable, value));
+        if let Some(substitutions) = substituted.get(&variable) {
+            let mut children = vec![id];
+            for s in substitutions {
+                children.push(self.push(Node::Literal(s.name, value != s.negated)));
+            }
+            id = self.push(Node::And(children));
+        }
+        literals.insert((variable, value), id);
+        id
+    }
+
+    /// Variable of a literal of the first child whose negation is a literal of the second one.
+    fn decision(&self, children: &[usize]) -> Option<u32> {
+        let [first, second] = children else {
+            return None;
+        };
+        let literals = |node: usize| -> Vec<(u32, bool)> {
+            let children = match &self.nodes[node] {
+                Node::And(children) => children.clone(),
+                _ => vec![node],
+            };
+            children.iter().filter_map(|c| self.literal_of(*c)).collect()
+        };
+        let second = literals(*second);
+        literals(*first).into_iter().find(|(v, b)| second.contains(&(*v, !*b))).map(|(v, _)| v)
+    }
+
+    /// Literal a node stands for, a literal extended by its substituted variables stands for its first literal.
+    fn literal_of(&self, node: usize) -> Option<(u32, bool)> {
+        match &self.nodes[node] {
+            Node::Literal(v, b) => Some((*v, *b)),
+            Node::And(children) => match children.first().map(|c| &self.nodes[*c]) {
+                Some(Node::Literal(v, b)) if children.iter().all(|c| matches!(self.nodes[*c], Node::Literal(..))) => Some((*v, *b)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Weighted count of the models that agree with all `assumptions`, contradicting assumptions leave no model.
+    pub fn count_weighted<W: Weights>(&self, weights: &W, assumptions: &[(u32, bool)]) -> W::Value {
+        self.values(weights, assumptions).swap_remove(self.root)
+    }
+
+    /// Number of models that agree with all `assumptions`.
+    pub fn count(&self, assumptions: &[(u32, bool)]) -> BigUint {
+        self.count_weighted(&Unweighted, assumptions)
+    }
+
+    /// Weighted counts of the models that agree with the `assumptions` and contain the positive or the
+    /// negative literal of every variable, indexed by variable. `None` for variables the circuit does not contain.
+    ///
+    /// One pass computes the derivative of the root with respect to every node, the count of a literal is
+    /// its derivative times its weight.
+    pub fn literal_counts<W: Weights>(&self, weights: &W, assumptions: &[(u32, bool)]) -> Vec<Option<(W::Value, W::Value)>> {
+        let values = self.values(weights, assumptions);
+        let mut derivatives = vec![W::Value::zero(); self.nodes.len()];
+        derivatives[self.root] = W::Value::one();
+        let mut counts: Vec<Option<(W::Value, W::Value)>> = vec![None; self.variables as usize];
+        for (i, node) in self.nodes.iter().enumerate().rev() {
+            let derivative = derivatives[i].clone();
+            match node {
+                Node::True | Node::False => {}
+                Node::Literal(v, b) => {
+                    let count = counts[*v as usize].get_or_insert_with(|| (W::Value::zero(), W::Value::zero()));
+                    let literal = if *b { &mut count.0 } else { &mut count.1 };
+                    *literal = literal.clone() + derivative * values[i].clone();
+                }
+                Node::Or(_, children) => {
+                    for c in children {
+                        derivatives[*c] = derivatives[*c].clone() + derivative.clone();
+                    }
+                }
+                Node::And(children) => {
+                    if derivative.is_zero() {
+                        continue;
+                    }
+                    // product of the siblings of every child without division, the values may be zero
+                    let mut suffix = vec![W::Value::one(); children.len() + 1];
+                    for (k, c) in children.iter().enumerate().rev() {
+                        suffix[k] = suffix[k + 1].clone() * values[*c].clone();
+                    }
+                    let mut prefix = derivative;
+                    for (k, c) in children.iter().enumerate() {
+                        derivatives[*c] = derivatives[*c].clone() + prefix.clone() * suffix[k + 1].clone();
+                        prefix = prefix * values[*c].clone();
+                    }
+                }
+            }
+        }
+        counts
+    }
+
+    /// Probability of the positive literal of every variable among the models that agree with the
+    /// `assumptions`, the probability of a model is proportional to its weight. `None` for variables the
+    /// circuit does not contain and for all variables if no model agrees with the assumptions.
+    pub fn marginals(&self, weights: &LiteralWeights, assumptions: &[(u32, bool)]) -> Vec<Option<BigRational>> {
+        let total = self.count_weighted(weights, assumptions);
+        self.literal_counts(weights, assumptions).into_iter().map(|count| {
+            if total.is_zero() {
+                return None;
+            }
+            count.map(|(positive, _)| positive / total.clone())
+        }).collect()
+    }
+
+    /// Weighted count of every node with the literals contradicting an assumption set to zero.
+    pub fn values<W: Weights>(&self, weights: &W, assumptions: &[(u32, bool)]) -> Vec<W::Value> {
+        let allowed = assumed(self.variables, assumptions);
+        let mut values: Vec<W::Value> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let value = match node {
+                Node::True => W::Value::one(),
+                Node::False => W::Value::zero(),
+                Node::Literal(v, b) if allowed[*v as usize][*b as usize] => weights.literal(*v, *b),
+                Node::Literal(..) => W::Value::zero(),
+                Node::And(children) => children.iter().fold(W::Value::one(), |p, c| p * values[*c].clone()),
+                Node::Or(_, children) => children.iter().fold(W::Value::zero(), |s, c| s + values[*c].clone()),
+            };
+            values.push(value);
+        }
+        values
+    }
+
+    pub fn edges(&self) -> usize {
+        self.nodes.iter().map(|n| match n {
+            Node::And(children) | Node::Or(_, children) => children.len(),
+            _ => 0,
+        }).sum()
+    }
+}
+
+/// Values every variable may take under the assumptions, indexed by variable and value.
+fn assumed(variables: u32, assumptions: &[(u32, bool)]) -> Vec<[bool; 2]> {
+    let mut allowed = vec![[true, true]; variables as usize];
+    for (v, b) in assumptions {
+        if let Some(a) = allowed.get_mut(*v as usize) {
+            a[!*b as usize] = false;
+        }
+    }
+    allowed
+}
+
+/// The c2d/d4 `.nnf` format: a header `nnf <nodes> <edges> <variables>` followed by one node per line,
+/// `L <literal>`, `A <k> <children>` and `O <decision variable or 0> <k> <children>`. Variable `i` is written as `i + 1`.
+impl Display for Ddnnf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let children = |children: &[usize]| children.iter().map(|c| format!(" {}", c)).collect::<String>();
+        writeln!(f, "nnf {} {} {}", self.nodes.len(), self.edges(), self.variables)?;
+        for node in &self.nodes {
+            match node {
+                Node::True => writeln!(f, "A 0")?,
+                Node::False => writeln!(f, "O 0 0")?,
+                Node::Literal(v, b) => writeln!(f, "L {}{}", if *b { "" } else { "-" }, v + 1)?,
+                Node::And(c) => writeln!(f, "A {}{}", c.len(), children(c))?,
+                Node::Or(d, c) => writeln!(f, "O {} {}{}", d.map_or(0, |v| v + 1), c.len(), children(c))?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+//! Enumeration of the satisfying assignments of a formula.
+
+use std::collections::{HashMap, HashSet};
+use crate::parsing::equation_ds::Substitution;
+use crate::solving::heuristics::{Heuristic, Occurrence};
+use crate::solving::pb_ds::{PBFormula, TrailFormula};
+use crate::solving::solver::satisfiable;
+
+/// Enumerates models with the search of [`crate::solving::solver::Solver::count`], without a cache
+/// every branch that contains a model is visited. Models are reported as they are found.
+#[derive(Clone, Debug)]
+pub struct Enumerator {
+    /// Variables the models are restricted to, every assignment to them is reported once. `None` reports all variables.
+    pub projection: Option<HashSet<u32>>,
+    /// Reports every value of the free variables, otherwise a free variable is a don't-care with value `None`.
+    pub expand_free: bool,
+    /// Free variables that are expanded even without `expand_free`.
+    pub expanded: HashSet<u32>,
+    /// Largest number of reported assignments, `None` reports all.
+    pub limit: Option<u64>,
+    /// Number of variables before preprocessing, the auxiliary variables it added are not reported.
+    /// `None` reports all variables of the formula.
+    pub variables: Option<u32>,
+    /// Variables preprocessing replaced by a literal, they are reported with the value of the literal.
+    pub substitutions: Vec<Substitution>,
+}
+
+impl Default for Enumerator {
+    fn default() -> Self {
+        Enumerator {
+            projection: None,
+            expand_free: true,
+            expanded: HashSet::new(),
+            limit: None,
+            variables: None,
+            substitutions: Vec::new(),
+        }
+    }
+}
+
+struct Search<'a, F> {
+    enumerator: &'a Enumerator,
+    /// Sorted variables the search assigns, the reported ones and the literals of the reported substituted ones.
+    variables: Vec<u32>,
+    /// Set of `variables`, the other variables only have to be satisfiable.
+    projection: HashSet<u32>,
+    /// Free variables that are expanded even without `expand_free`.
+    expanded: HashSet<u32>,
+    /// Sorted reported variables, with their substitution if they were substituted.
+    reported_variables: Vec<(u32, Option<Substitution>)>,
+    assignment: HashMap<u32, bool>,
+    visit: F,
+    reported: u64,
+    stopped: bool,
+}
+
+impl Enumerator {
+    pub fn new() -> Enumerator {
+        Enumerator::default()
+    }
+
+    /// Calls `visit` with every satisfying assignment to the variables of the formula and the substituted
+    /// variables (or the projection), sorted by variable. Stops after `limit` assignments or once `visit`
+    /// returns `false`, returns the number of reported assignments.
+    pub fn enumerate<F>(&self, formula: &PBFormula, visit: F) -> u64
+    where
+        F: FnMut(&[(u32, Option<bool>)]) -> bool,
+    {
+        let substitutions: HashMap<u32, Substitution> = self.substitutions.iter().map(|s| (s.name, *s)).collect();
+        let mut reported_variables: Vec<(u32, Option<Substitution>)> = formula.variables.iter().copied()
+            .chain(substitutions.keys().copied())
+            .filter(|v| self.variables.is_none_or(|n| *v < n))
+            .filter(|v| self.projection.as_ref().is_none_or(|p| p.contains(v)))
+            .map(|v| (v, substitutions.get(&v).copied()))
+            .collect();
+        reported_variables.sort_unstable_by_key(|(v, _)| *v);
+        reported_variables.dedup_by_key(|(v, _)| *v);
+        // a substituted variable takes the value of its literal, which therefore is no don't-care
+        let mut expanded = self.expanded.clone();
+        expanded.extend(reported_variables.iter().filter_map(|(_, s)| s.map(|s| s.literal)));
+        let mut variables: Vec<u32> = reported_variables.iter().map(|(v, s)| s.map_or(*v, |s| s.literal)).collect();
+        variables.sort_unstable();
+        variables.dedup();
+        let mut search = Search {
+            enumerator: self,
+            projection: variables.iter().copied().collect(),
+            variables,
+            expanded,
+            reported_variables,
+            assignment: HashMap::new(),
+            visit,
+            reported: 0,
+            stopped: self.limit == Some(0),
+        };
+        // literals forced before the first decision
+        let (formula, implied) = formula.clone().propagate();
+        if formula.contains_false_clause() || search.stopped {
+            return 0;
+        }
+        search.assignment.extend(implied);
+        let mut trail_formula = TrailFormula::new(&formula);
+        search.search(&mut trail_formula);
+        search.reported
+    }
+}
+
+impl<F: FnMut(&[(u32, Option<bool>)]) -> bool> Search<'_, F> {
+    fn search(&mut self, formula: &mut TrailFormula) {
+        let (variables, constraints) = (formula.unassigned(), formula.active_constraints());
+        let residual = formula.residual(&variables, &constraints);
+        let l = match Occurrence.next_variable(&residual, Some(&self.projection)) {
+            Some(l) => l,
+            None => {
+                // the remaining constraints only contain variables outside of the projection
+                if residual.is_empty() || satisfiable(&residual.to_formula()) {
+                    self.report();
+                }
+                return;
+            }
+        };
+        for value in [true, false] {
+            if self.stopped {
+                return;
+            }
+            let Ok(implied) = formula.decide(l, value) else {
+                continue;
+            };
+            self.assignment.insert(l, value);
+            self.assignment.extend(implied.iter().copied());
+            self.search(formula);
+            self.assignment.remove(&l);
+            for (v, _) in &implied {
+                self.assignment.remove(v);
+            }
+            formula.undo();
+        }
+    }
+
+    /// Reports the current assignment, the unassigned variables are free.
+    fn report(&mut self) {
+        let mut model: Vec<(u32, Option<bool>)> = self.variables.iter().map(|v| (*v, self.assignment.get(v).copied())).collect();
+        let expand: Vec<usize> = model.iter().enumerate()
+            .filter(|(_, (v, b))| b.is_none() && (self.enumerator.expand_free || self.expanded.contains(v)))
+            .map(|(i, _)| i)
+            .collect();
+        self.expand(&mut model, &expand);
+    }
+
+    fn expand(&mut self, model: &mut [(u32, Option<bool>)], positions: &[usize]) {
+        let Some((p, rest)) = positions.split_first() else {
+            if !self.stopped {
+                let value = |v: u32| model[model.binary_search_by_key(&v, |(u, _)| *u).unwrap()].1;
+                let reported: Vec<(u32, Option<bool>)> = self.reported_variables.iter().map(|(v, s)| match s {
+                    Some(s) => (*v, value(s.literal).map(|b| b != s.negated)),
+                    None => (*v, value(*v)),
+                }).collect();
+                self.stopped = !(self.visit)(&reported);
+                self.reported += 1;
+                self.stopped |= self.enumerator.limit.is_some_and(|l| self.reported >= l);
+            }
+            return;
+        };
+        for value in [true, false] {
+            if self.stopped {
+                break;
+            }
+            model[*p].1 = Some(value);
+            self.expand(model, rest);
+        }
+        model[*p].1 = None;
+    }
+}
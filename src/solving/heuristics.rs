@@ -0,0 +1,157 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use crate::solving::ordering::VariableOrder;
+use crate::solving::pb_ds::Residual;
+use crate::solving::solver::formula_to_disconnected_formula;
+
+/// Decision heuristic of the search. The solvers share one heuristic between all branches and
+/// threads, so heuristics that learn from the search keep their state behind a lock.
+pub trait Heuristic {
+    /// Picks the next decision variable among the projected variables of the constraints.
+    /// Returns `None` if no constraint contains a projected variable.
+    fn next_variable(&self, formula: &Residual, projection: Option<&HashSet<u32>>) -> Option<u32>;
+
+    /// Called with the decisions a conflict follows from.
+    fn conflict(&self, _reason: &[u32]) {}
+}
+
+impl<H: Heuristic + ?Sized> Heuristic for Arc<H> {
+    fn next_variable(&self, formula: &Residual, projection: Option<&HashSet<u32>>) -> Option<u32> {
+        (**self).next_variable(formula, projection)
+    }
+
+    fn conflict(&self, reason: &[u32]) {
+        (**self).conflict(reason)
+    }
+}
+
+/// Most frequent variable of the formula.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Occurrence;
+
+impl Heuristic for Occurrence {
+    fn next_variable(&self, formula: &Residual, projection: Option<&HashSet<u32>>) -> Option<u32> {
+        best(candidates(formula, projection).map(|v| (v, formula.occurrences(v).count() as f64)))
+    }
+}
+
+/// Variable with the largest sum of coefficients, every coefficient relative to the degree of its
+/// clause, so variables that decide a clause on their own count the most.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WeightedOccurrence;
+
+impl Heuristic for WeightedOccurrence {
+    fn next_variable(&self, formula: &Residual, projection: Option<&HashSet<u32>>) -> Option<u32> {
+        best(candidates(formula, projection).map(|v| {
+            let score = formula.occurrences(v).map(|(c, factor)| (factor.abs() as f64 / formula.degree(c).max(1) as f64).min(1.0)).sum();
+            (v, score)
+        }))
+    }
+}
+
+/// Variable whose positive assignment leaves the smallest largest component. Copies the formula and
+/// counts its components once for every candidate, so it is only practical for small formulas.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BestPartition;
+
+impl Heuristic for BestPartition {
+    fn next_variable(&self, residual: &Residual, projection: Option<&HashSet<u32>>) -> Option<u32> {
+        let formula = residual.to_formula();
+        let mut best_index = None;
+        let mut best_value = usize::MAX;
+        for variable in candidates(residual, projection) {
+            let (potential_new_formula, _) = formula.get_sub_formula(variable, true);
+            let potential_new_partition = formula_to_disconnected_formula(potential_new_formula, None);
+
+            let mut max_number_clauses = 0;
+            for partition in &potential_new_partition.partitions {
+                if partition.formula.clauses.len() > max_number_clauses {
+                    max_number_clauses = partition.formula.clauses.len();
+                }
+            }
+            if max_number_clauses < best_value {
+                best_value = max_number_clauses;
+                best_index = Some(variable);
+            }
+        }
+        best_index
+    }
+}
+
+impl Heuristic for VariableOrder {
+    fn next_variable(&self, formula: &Residual, projection: Option<&HashSet<u32>>) -> Option<u32> {
+        candidates(formula, projection).min_by_key(|v| (self.rank(*v), *v))
+    }
+}
+
+struct Activity {
+    scores: HashMap<u32, f64>,
+    increment: f64,
+}
+
+/// Variable state aware decaying sum: the activity of the variables in recent conflicts combined
+/// with the number of occurrences in the formula (DLCS), `activity + occurrences / 2`.
+pub struct Vsads {
+    activity: Mutex<Activity>,
+    /// Factor the activity of all variables decays with per conflict.
+    pub decay: f64,
+}
+
+impl Default for Vsads {
+    fn default() -> Self {
+        Vsads {
+            activity: Mutex::new(Activity {
+                scores: HashMap::new(),
+                increment: 1.0,
+            }),
+            decay: 0.95,
+        }
+    }
+}
+
+impl Heuristic for Vsads {
+    fn next_variable(&self, formula: &Residual, projection: Option<&HashSet<u32>>) -> Option<u32> {
+        let activity = self.activity.lock().unwrap();
+        best(candidates(formula, projection).map(|v| {
+            // activities are kept relative to the current increment, which grows instead of decaying all scores
+            let score = 0.5 * formula.occurrences(v).count() as f64 + activity.scores.get(&v).map_or(0.0, |a| a / activity.increment);
+            (v, score)
+        }))
+    }
+
+    fn conflict(&self, reason: &[u32]) {
+        let mut activity = self.activity.lock().unwrap();
+        let increment = activity.increment;
+        for variable in reason {
+            *activity.scores.entry(*variable).or_default() += increment;
+        }
+        activity.increment /= self.decay;
+        if activity.increment > 1e100 {
+            for score in activity.scores.values_mut() {
+                *score *= 1e-100;
+            }
+            activity.increment *= 1e-100;
+        }
+    }
+}
+
+/// Projected variables that occur in an unsatisfied constraint, in increasing order.
+fn candidates<'a>(formula: &'a Residual, projection: Option<&'a HashSet<u32>>) -> impl Iterator<Item = u32> + 'a {
+    formula.variables.iter().copied()
+        .filter(move |v| projection.is_none_or(|p| p.contains(v)))
+        .filter(|v| formula.occurrences(*v).next().is_some())
+}
+
+/// Variable with the highest score. The scores come in increasing order of the variables and ties are
+/// broken towards the smallest one, so the choice is deterministic.
+fn best(scores: impl Iterator<Item = (u32, f64)>) -> Option<u32> {
+    let mut best_index = None;
+    let mut best_value = f64::NEG_INFINITY;
+    for (variable, score) in scores {
+        if score > best_value {
+            best_value = score;
+            best_index = Some(variable);
+        }
+    }
+    best_index
+}
@@ -0,0 +1,140 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use crate::solving::pb_ds::PBFormula;
+
+/// Heuristic that picks the next variable to eliminate from the primal graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EliminationHeuristic {
+    /// Eliminates the variable whose elimination adds the fewest edges.
+    MinFill,
+    /// Eliminates the variable with the fewest neighbours.
+    MinDegree,
+}
+
+/// Static branching order computed once from a tree decomposition of the primal graph, in which two
+/// variables are adjacent if they share a clause. The decomposition is given by an elimination order,
+/// variables near the root of its elimination tree separate the formula into independent parts, so
+/// they are ranked first and the search splits into components early.
+pub struct VariableOrder {
+    rank: HashMap<u32, usize>,
+    /// Width of the tree decomposition, the largest bag has `width + 1` variables.
+    pub width: usize,
+}
+
+impl VariableOrder {
+    pub fn new(formula: &PBFormula, heuristic: EliminationHeuristic) -> VariableOrder {
+        let mut variables: Vec<u32> = Vec::new();
+        let mut index: HashMap<u32, usize> = HashMap::new();
+        for clause in &formula.clauses {
+            for literal in &clause.literals {
+                index.entry(literal.name).or_insert_with(|| {
+                    variables.push(literal.name);
+                    variables.len() - 1
+                });
+            }
+        }
+        let mut adjacency: Vec<HashSet<usize>> = vec![HashSet::new(); variables.len()];
+        for clause in &formula.clauses {
+            for a in &clause.literals {
+                for b in &clause.literals {
+                    if a.name != b.name {
+                        adjacency[index[&a.name]].insert(index[&b.name]);
+                    }
+                }
+            }
+        }
+
+        let (order, neighbours, width) = eliminate(adjacency, heuristic);
+
+        // the parent in the elimination tree is the neighbour that is eliminated next
+        let mut position = vec![0; order.len()];
+        for (p, v) in order.iter().enumerate() {
+            position[*v] = p;
+        }
+        let mut depth = vec![0; order.len()];
+        for v in order.iter().rev() {
+            if let Some(parent) = neighbours[*v].iter().min_by_key(|u| position[**u]) {
+                depth[*v] = depth[*parent] + 1;
+            }
+        }
+        let mut ranked: Vec<usize> = order.clone();
+        ranked.sort_by_key(|v| (depth[*v], std::cmp::Reverse(position[*v])));
+
+        VariableOrder {
+            rank: ranked.iter().enumerate().map(|(r, v)| (variables[*v], r)).collect(),
+            width,
+        }
+    }
+
+    /// Position of the variable in the branching order, variables that occur in no clause come last.
+    pub fn rank(&self, variable: u32) -> usize {
+        match self.rank.get(&variable) {
+            Some(r) => *r,
+            None => usize::MAX
+        }
+    }
+}
+
+/// Eliminates all vertices and returns the elimination order, the neighbours of every vertex at the
+/// time it was eliminated and the width of the resulting tree decomposition.
+fn eliminate(mut adjacency: Vec<HashSet<usize>>, heuristic: EliminationHeuristic) -> (Vec<usize>, Vec<Vec<usize>>, usize) {
+    let score = |adjacency: &Vec<HashSet<usize>>, v: usize| -> usize {
+        match heuristic {
+            EliminationHeuristic::MinDegree => adjacency[v].len(),
+            EliminationHeuristic::MinFill => {
+                let neighbours: Vec<usize> = adjacency[v].iter().copied().collect();
+                let mut fill = 0;
+                for (i, a) in neighbours.iter().enumerate() {
+                    for b in &neighbours[i + 1..] {
+                        if !adjacency[*a].contains(b) {
+                            fill += 1;
+                        }
+                    }
+                }
+                fill
+            }
+        }
+    };
+
+    let n = adjacency.len();
+    let mut scores: Vec<usize> = (0..n).map(|v| score(&adjacency, v)).collect();
+    let mut queue: BTreeSet<(usize, usize)> = (0..n).map(|v| (scores[v], v)).collect();
+    let mut eliminated = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    let mut neighbours = vec![Vec::new(); n];
+    let mut width = 0;
+
+    while let Some((_, v)) = queue.pop_first() {
+        let adjacent: Vec<usize> = adjacency[v].iter().copied().collect();
+        width = width.max(adjacent.len());
+        for (i, a) in adjacent.iter().enumerate() {
+            adjacency[*a].remove(&v);
+            for b in &adjacent[i + 1..] {
+                adjacency[*a].insert(*b);
+                adjacency[*b].insert(*a);
+            }
+        }
+        eliminated[v] = true;
+        order.push(v);
+
+        // the fill of a vertex changes if it or one of its neighbours gained or lost an edge
+        let mut affected: HashSet<usize> = adjacent.iter().copied().collect();
+        if heuristic == EliminationHeuristic::MinFill {
+            for a in &adjacent {
+                affected.extend(adjacency[*a].iter().copied());
+            }
+        }
+        for u in affected {
+            if eliminated[u] {
+                continue;
+            }
+            let s = score(&adjacency, u);
+            if s != scores[u] {
+                queue.remove(&(scores[u], u));
+                scores[u] = s;
+                queue.insert((s, u));
+            }
+        }
+        neighbours[v] = adjacent;
+    }
+    (order, neighbours, width)
+}
@@ -0,0 +1,230 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use num_traits::{One, Zero};
+use rayon::prelude::*;
+use crate::solving::cache::{CacheStats, ConcurrentCache};
+use crate::solving::heuristics::{Heuristic, Occurrence};
+use crate::solving::pb_ds::{Component, PBFormula, TrailFormula};
+use crate::solving::solver::{count_unprojected, projected_free, satisfiable, weigh_implied, SearchStats, Solver};
+use crate::solving::weights::Weights;
+
+/// Counts like [`Solver`], but the first `split_depth` decisions and the components between them are
+/// counted concurrently on the rayon thread pool the solver is called from. Every concurrent branch works
+/// on its own [`TrailFormula::fork`], below `split_depth` decisions a sequential [`Solver`] counts the
+/// rest with the trail and learns on its own copy.
+///
+/// The tasks share a cache for counts no learned constraint contributed to, see
+/// [`Solver::count_disconnected_components`] for why other counts are only valid within their task.
+pub struct ParallelSolver<W: Weights> {
+    pub weights: W,
+    /// Cache shared by all tasks.
+    pub cache: Arc<ConcurrentCache<W::Value>>,
+    /// Variables the count is projected on, `None` counts over all variables.
+    pub projection: Option<HashSet<u32>>,
+    /// Decision heuristic shared by all threads, the most frequent variable by default.
+    pub heuristic: Arc<dyn Heuristic + Send + Sync>,
+    /// Number of decision levels whose branches are counted in parallel.
+    pub split_depth: u32,
+    /// Memory budget of the cache of every task in bytes.
+    pub task_cache_memory: usize,
+    stats: Mutex<(SearchStats, CacheStats)>,
+}
+
+impl<W> ParallelSolver<W>
+where
+    W: Weights + Sync,
+    W::Value: Send,
+{
+    /// Creates a solver for `threads` threads whose caches use at most about `cache_memory` bytes together,
+    /// half of it is shared and the other half is split between the tasks running at the same time.
+    pub fn new(weights: W, cache_memory: usize, threads: usize) -> ParallelSolver<W> {
+        let threads = threads.max(1);
+        ParallelSolver {
+            weights,
+            cache: Arc::new(ConcurrentCache::new(cache_memory / 2, threads * 4)),
+            projection: None,
+            heuristic: Arc::new(Occurrence),
+            // about 16 tasks per thread balance branches of different size
+            split_depth: threads.ilog2() + 5,
+            task_cache_memory: cache_memory / 2 / threads,
+            stats: Mutex::new((SearchStats::default(), CacheStats::default())),
+        }
+    }
+
+    /// Conflict analysis of all tasks.
+    pub fn stats(&self) -> SearchStats {
+        self.stats.lock().unwrap().0
+    }
+
+    /// Statistics of the shared cache plus those of the caches of all tasks.
+    pub fn cache_stats(&self) -> CacheStats {
+        let shared = self.cache.stats();
+        let tasks = self.stats.lock().unwrap().1;
+        CacheStats {
+            hits: shared.hits + tasks.hits,
+            misses: shared.misses + tasks.misses,
+            evictions: shared.evictions + tasks.evictions,
+            invalidated: shared.invalidated + tasks.invalidated,
+            entries: shared.entries,
+            memory: shared.memory,
+        }
+    }
+
+    fn free(&self, variables: &[u32]) -> W::Value {
+        projected_free(&self.weights, self.projection.as_ref(), variables)
+    }
+
+    fn implied(&self, implied: &[(u32, bool)], count: W::Value) -> W::Value {
+        weigh_implied(&self.weights, self.projection.as_ref(), implied, count)
+    }
+
+    fn conflict(&self, reason: &[u32]) -> W::Value {
+        self.stats.lock().unwrap().0.conflicts += 1;
+        self.heuristic.conflict(reason);
+        W::Value::zero()
+    }
+
+    /// Adds the constraints a fork learned to the statistics.
+    fn learned(&self, formula: &TrailFormula) {
+        self.stats.lock().unwrap().0.learned += formula.learned();
+    }
+
+    /// Sequential solver of a task, it reads and writes the shared cache.
+    fn task(&self) -> Solver<&W> {
+        let mut solver = Solver::new(&self.weights, self.task_cache_memory);
+        solver.projection = self.projection.clone();
+        solver.heuristic = Box::new(self.heuristic.clone());
+        solver.shared = Some(self.cache.clone());
+        solver
+    }
+
+    fn finish(&self, solver: Solver<&W>) {
+        let mut stats = self.stats.lock().unwrap();
+        let (search, cache) = &mut *stats;
+        search.conflicts += solver.stats.conflicts;
+        search.backjumps += solver.stats.backjumps;
+        let s = solver.cache.stats();
+        cache.hits += s.hits;
+        cache.misses += s.misses;
+        cache.evictions += s.evictions;
+        cache.invalidated += s.invalidated;
+    }
+
+    /// Counts without component decomposition.
+    pub fn count(&self, formula: &PBFormula) -> W::Value {
+        if let Some(reason) = formula.conflict_reason() {
+            return self.conflict(reason);
+        }
+        let mut trail_formula = TrailFormula::new(formula);
+        let res = self.count_plain(&mut trail_formula, 0);
+        self.learned(&trail_formula);
+        res
+    }
+
+    fn count_plain(&self, formula: &mut TrailFormula, depth: u32) -> W::Value {
+        if depth >= self.split_depth {
+            let mut solver = self.task();
+            let res = solver.count_plain(formula, None).0;
+            self.finish(solver);
+            return res;
+        }
+        let whole = Component {
+            variables: formula.unassigned(),
+            constraints: formula.active_constraints(),
+        };
+        self.branch(formula, &whole, false, depth)
+    }
+
+    /// Decides a variable of a subformula that is not cached yet and counts both branches at the same time,
+    /// like [`Solver`] but without backjumping. With `decompose` the branches are split into components again
+    /// and the count is not cached, because the tasks below may have used learned constraints.
+    fn branch(&self, formula: &mut TrailFormula, component: &Component, decompose: bool, depth: u32) -> W::Value {
+        let residual = formula.residual(&component.variables, &component.constraints);
+        if residual.is_empty() {
+            return self.free(residual.variables);
+        }
+        let key = residual.cache_key();
+        if let Some(c) = self.cache.get(&key) {
+            return c;
+        }
+        let l = match self.heuristic.next_variable(&residual, self.projection.as_ref()) {
+            Some(l) => l,
+            None => return count_unprojected(&self.weights, self.projection.as_ref(), &residual),
+        };
+        let mut other = formula.fork();
+        let (c1, c2) = rayon::join(
+            || self.decision(formula, component, decompose, l, true, depth),
+            || self.decision(&mut other, component, decompose, l, false, depth),
+        );
+        self.learned(&other);
+        let res = self.weights.decision(l, true, c1) + self.weights.decision(l, false, c2);
+        if !decompose {
+            // without components the formula is the whole subformula, so learned constraints never change its count
+            self.cache.insert(key, res.clone());
+        }
+        res
+    }
+
+    /// Decides `variable` and counts the rest one level deeper, literals outside `component` are weighed elsewhere.
+    fn decision(&self, formula: &mut TrailFormula, component: &Component, decompose: bool, variable: u32, value: bool, depth: u32) -> W::Value {
+        let mut implied = match formula.decide(variable, value) {
+            Ok(implied) => implied,
+            Err(reason) => return self.conflict(&reason),
+        };
+        implied.retain(|(v, _)| component.variables.binary_search(v).is_ok());
+        let c = if decompose {
+            self.count_components(formula, &component.variables, depth + 1)
+        } else {
+            self.count_plain(formula, depth + 1)
+        };
+        formula.undo();
+        self.implied(&implied, c)
+    }
+
+    /// Counts the components of every subformula independently.
+    pub fn count_disconnected_components(&self, formula: &PBFormula) -> W::Value {
+        if let Some(reason) = formula.conflict_reason() {
+            return self.conflict(reason);
+        }
+        let mut trail_formula = TrailFormula::new(formula);
+        let res = self.count_components(&mut trail_formula, &formula.variables, 0);
+        self.learned(&trail_formula);
+        res
+    }
+
+    fn count_components(&self, formula: &mut TrailFormula, variables: &[u32], depth: u32) -> W::Value {
+        if depth >= self.split_depth {
+            let mut solver = self.task();
+            let res = solver.count_components(formula, variables, None).0;
+            self.finish(solver);
+            return res;
+        }
+        let (components, free) = formula.components(variables);
+        let mut projected = Vec::new();
+        for component in components {
+            if self.projection.as_ref().is_some_and(|p| !component.variables.iter().any(|v| p.contains(v))) {
+                // components without projected variables only have to be satisfiable
+                if !satisfiable(&formula.residual(&component.variables, &component.constraints).to_formula()) {
+                    return W::Value::zero();
+                }
+            } else {
+                projected.push(component);
+            }
+        }
+        let res = self.free(&free);
+        match projected.len() {
+            0 => return res,
+            1 => return res * self.branch(formula, &projected[0], true, depth),
+            _ => {}
+        }
+        let mut forks: Vec<TrailFormula> = projected.iter().map(|_| formula.fork()).collect();
+        let product = projected.par_iter()
+            .zip(forks.par_iter_mut())
+            .map(|(component, fork)| self.branch(fork, component, true, depth))
+            .reduce(W::Value::one, |a, b| a * b);
+        for fork in &forks {
+            self.learned(fork);
+        }
+        res * product
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use crate::parsing::equation_ds::File;
 use crate::parsing::equation_ds::EquationPart;
 
@@ -7,7 +7,8 @@ use crate::parsing::equation_ds::EquationPart;
 #[derive(Debug)]
 #[derive(Clone)]
 pub struct PBFormula{
-    pub n: u32,
+    /// Sorted indices of the unassigned variables, variables that occur in no clause are free.
+    pub variables: Vec<u32>,
     pub clauses: Vec<Clause>
 }
 
@@ -16,8 +17,10 @@ pub struct PBFormula{
 #[derive(Hash)]
 #[derive(Clone)]
 pub struct Clause{
-    pub rhs: i32,
-    pub literals: Vec<Literal>
+    pub rhs: i64,
+    pub literals: Vec<Literal>,
+    /// Sorted decision variables whose assignments derived this clause from its original constraint.
+    pub reason: Vec<u32>
 }
 
 #[derive(Debug)]
@@ -26,59 +29,62 @@ pub struct Clause{
 #[derive(Clone)]
 pub struct Literal{
     pub name: u32,
-    pub factor: i32,
+    pub factor: i64,
 }
 
 pub fn new(file: &File) -> PBFormula {
+    // substituted variables are determined by their literal and must not count as free
+    let substituted: HashSet<u32> = file.substitutions.iter().map(|s| s.name).collect();
     let mut pb_formula = PBFormula{
-        n: file.name_map.len() as u32,
+        variables: (0..file.variable_count()).filter(|v| !substituted.contains(v)).collect(),
         clauses: Vec::new()
     };
 
     for equation in &file.equations {
         let mut rhs = 0;
-        match equation.e2.literals.get(0).unwrap() {
-            EquationPart::Literal { .. } => {
-                ();
-            } EquationPart::Factor(f) => {
-                rhs = *f;
-            }
+        if let EquationPart::Factor(f) = equation.e2.literals.first().unwrap() {
+            rhs = *f;
         }
         let mut clause = Clause{
-            rhs: rhs,
-            literals: Vec::new()
+            rhs,
+            literals: Vec::new(),
+            reason: Vec::new()
         };
         for equation_part in &equation.e1.literals {
             match equation_part {
-                EquationPart::Literal { factor, name } => {
+                EquationPart::Literal { factor, name, negated: false } => {
                     clause.literals.push(Literal{
                         name: *name,
                         factor: *factor
                     })
-                } EquationPart::Factor(_) => {
-                    ();
                 }
+                // a*~x = a - a*x
+                EquationPart::Literal { factor, name, negated: true } => {
+                    clause.literals.push(Literal{
+                        name: *name,
+                        factor: -*factor
+                    });
+                    clause.rhs -= *factor;
+                }
+                EquationPart::Factor(_) => {}
+                EquationPart::Product { .. } => unreachable!("products are linearized during preprocessing")
             }
         }
-        pb_formula.clauses.push(clause);
+        // clauses that hold under every assignment would never be removed by get_sub_formula
+        if clause.get_max_neg_imp() < clause.rhs {
+            pb_formula.clauses.push(clause);
+        }
     }
 
     pb_formula
 }
 
 impl Clause {
-    fn contains(&self, literal_index: u32) -> bool {
-        for l in &self.literals{
-            if l.name == literal_index { return true }
-        }
-        false
-    }
-
-    fn get_max_pos_imp(&self) -> i32{
+    fn get_max_pos_imp(&self) -> i64{
         self.literals.iter().map(|x| x.factor).filter(|x| x > &0).sum()
     }
 
-    fn get_max_neg_imp(&self) -> i32{
+    fn get_max_neg_imp(&self) -> i64{
         self.literals.iter().map(|x| x.factor).filter(|x| x < &0).sum()
     }
     pub(crate) fn has_variable_overlap(&self, variables: &HashSet<u32>) -> bool {
@@ -87,42 +93,147 @@ impl Clause {
                 return true;
             }
         }
-        return false;
+        false
     }
 }
 
 impl PBFormula {
+    pub fn n(&self) -> u32 {
+        self.variables.len() as u32
+    }
+
+    /// Canonical encoding of the formula used as cache key. Literals and clauses are sorted and duplicate
+    /// clauses are dropped, so formulas that only differ in the order of their clauses and literals share a key.
+    /// Every clause is prefixed with its length, which makes the encoding unambiguous.
+    pub fn cache_key(&self) -> Box<[i64]> {
+        let mut clauses: Vec<Vec<i64>> = self.clauses.iter().map(|c| {
+            let mut literals: Vec<(u32, i64)> = c.literals.iter().map(|l| (l.name, l.factor)).collect();
+            literals.sort_unstable();
+            let mut encoded = Vec::with_capacity(2 + 2 * literals.len());
+            encoded.push(literals.len() as i64);
+            encoded.push(c.rhs);
+            for (name, factor) in literals {
+                encoded.push(name as i64);
+                encoded.push(factor);
+            }
+            encoded
+        }).collect();
+        clauses.sort_unstable();
+        clauses.dedup();
+
+        let mut key = Vec::with_capacity(1 + self.variables.len() + clauses.iter().map(|c| c.len()).sum::<usize>());
+        key.push(self.variables.len() as i64);
+        key.extend(self.variables.iter().map(|v| *v as i64));
+        for c in clauses {
+            key.extend(c);
+        }
+        key.into_boxed_slice()
+    }
+
     pub fn contains_false_clause(&self) -> bool {
+        self.conflict_reason().is_some()
+    }
+
+    /// Decisions that falsified a clause of the formula, `None` if no clause is false.
+    pub fn conflict_reason(&self) -> Option<&[u32]> {
         for c in &self.clauses {
             if c.literals.is_empty() && c.rhs > 0 {
-                return true;
+                return Some(&c.reason);
             }
         }
-        false
+        None
+    }
+
+    /// Decisions that derived any clause of the formula, if the formula is unsatisfiable
+    /// these decisions alone make the original formula unsatisfiable.
+    pub fn reason(&self) -> Vec<u32> {
+        let mut reason = Vec::new();
+        for c in &self.clauses {
+            reason = merge_reasons(&reason, &c.reason);
+        }
+        reason
+    }
+
+    /// Assigns `literal_index` to `take` and unit propagates the result. Returns the remaining formula
+    /// and the literals implied by the propagation, on a conflict the formula contains a false clause.
+    /// The clauses record the decision, see [`Clause::reason`].
+    pub fn get_sub_formula(&self, literal_index: u32, take: bool) -> (PBFormula, Vec<(u32, bool)>) {
+        self.assign(&HashMap::from([(literal_index, (take, vec![literal_index]))])).propagate()
+    }
+
+    /// Assigns every literal whose coefficient exceeds the slack of its clause, the amount by which the
+    /// largest possible left side exceeds the right side, until no such literal is left.
+    pub fn propagate(self) -> (PBFormula, Vec<(u32, bool)>) {
+        let mut formula = self;
+        let mut implied = Vec::new();
+        loop {
+            if let Some(reason) = formula.conflict_reason() {
+                let reason = reason.to_vec();
+                return (formula.conflict(reason), implied);
+            }
+            let mut assignment: HashMap<u32, (bool, Vec<u32>)> = HashMap::new();
+            for c in &formula.clauses {
+                let slack = c.get_max_pos_imp() - c.rhs;
+                for l in &c.literals {
+                    if l.factor.abs() > slack {
+                        // a positive literal has to be set, a negative one must stay unset
+                        let value = l.factor > 0;
+                        match assignment.get(&l.name) {
+                            Some((other, reason)) if *other != value => {
+                                let reason = merge_reasons(reason, &c.reason);
+                                return (formula.conflict(reason), implied);
+                            }
+                            Some(_) => {}
+                            None => {
+                                assignment.insert(l.name, (value, c.reason.clone()));
+                            }
+                        }
+                    }
+                }
+            }
+            if assignment.is_empty() {
+                return (formula, implied);
+            }
+            formula = formula.assign(&assignment);
+            implied.extend(assignment.into_iter().map(|(v, (value, _))| (v, value)));
+        }
     }
 
-    pub fn get_sub_formula(&self, literal_index: u32, take: bool) -> PBFormula {
-        if self.n == 0{
-            println!("test");
+    /// Formula without models over the same variables.
+    fn conflict(self, reason: Vec<u32>) -> PBFormula {
+        PBFormula{
+            variables: self.variables,
+            clauses: vec![Clause{
+                rhs: 1,
+                literals: Vec::new(),
+                reason
+            }]
         }
+    }
+
+    /// Assigns the variables to the given values, every value comes with the decisions it follows from.
+    fn assign(&self, assignment: &HashMap<u32, (bool, Vec<u32>)>) -> PBFormula {
         let mut new_formula = PBFormula{
-            n: self.n - 1,
+            variables: self.variables.iter().copied().filter(|v| !assignment.contains_key(v)).collect(),
             clauses: Vec::new()
         };
 
         for c in &self.clauses {
-            if c.contains(literal_index) {
+            if c.literals.iter().any(|l| assignment.contains_key(&l.name)) {
                 let mut new_clause = Clause{
                     rhs: c.rhs,
-                    literals: Vec::new()
+                    literals: Vec::new(),
+                    reason: c.reason.clone()
                 };
                 for l in &c.literals {
-                    if l.name == literal_index {
-                        if take {
-                            new_clause.rhs -= l.factor;
+                    match assignment.get(&l.name) {
+                        Some((value, reason)) => {
+                            if *value {
+                                new_clause.rhs -= l.factor;
+                            }
+                            new_clause.reason = merge_reasons(&new_clause.reason, reason);
                         }
-                    }else{
-                        new_clause.literals.push(l.clone());
+                        None => new_clause.literals.push(l.clone())
                     }
                 }
 
@@ -130,7 +241,7 @@ impl PBFormula {
                     new_clause.literals = Vec::new();
                     new_clause.rhs = 1;
                 }
-                if !((new_clause.literals.len() == 0 && new_clause.rhs <= 0) || new_clause.get_max_neg_imp() >= new_clause.rhs){
+                if !((new_clause.literals.is_empty() && new_clause.rhs <= 0) || new_clause.get_max_neg_imp() >= new_clause.rhs){
                     new_formula.clauses.push(new_clause);
                 }
             }else{
@@ -141,4 +252,503 @@ impl PBFormula {
 
         new_formula
     }
-}
\ No newline at end of file
+}
+
+/// Union of two sorted lists of decision variables.
+pub(crate) fn merge_reasons(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] {
+            result.push(a[i]);
+            i += 1;
+        } else if b[j] < a[i] {
+            result.push(b[j]);
+            j += 1;
+        } else {
+            result.push(a[i]);
+            i += 1;
+            j += 1;
+        }
+    }
+    result.extend_from_slice(&a[i..]);
+    result.extend_from_slice(&b[j..]);
+    result
+}
+
+/// Number of constraints a [`TrailFormula`] learns, later conflicts are not learned.
+const MAX_LEARNED: usize = 100_000;
+
+/// Constraint of a [`TrailFormula`] with counters that are updated on every assignment.
+#[derive(Clone)]
+struct Constraint {
+    literals: Vec<Literal>,
+    /// Right side minus the coefficients of the literals assigned true.
+    degree: i64,
+    /// Sum of the positive coefficients of the unassigned literals.
+    positive: i64,
+    /// Sum of the negative coefficients of the unassigned literals.
+    negative: i64,
+    /// Largest absolute coefficient, the literals are only checked for propagation if it exceeds the slack.
+    max_factor: i64,
+}
+
+impl Constraint {
+    fn satisfied(&self) -> bool {
+        self.negative >= self.degree
+    }
+
+    fn falsified(&self) -> bool {
+        self.positive < self.degree
+    }
+
+    fn slack(&self) -> i64 {
+        self.positive - self.degree
+    }
+
+    /// Applies (`sign` 1) or reverts (`sign` -1) the assignment of a literal with coefficient `factor`.
+    fn update(&mut self, factor: i64, value: bool, sign: i64) {
+        if factor > 0 {
+            self.positive -= sign * factor;
+        } else {
+            self.negative -= sign * factor;
+        }
+        if value {
+            self.degree -= sign * factor;
+        }
+    }
+}
+
+/// Connected part of the unsatisfied constraints of a [`TrailFormula`] over its unassigned variables.
+pub struct Component {
+    /// Sorted unassigned variables of the constraints.
+    pub variables: Vec<u32>,
+    /// Sorted indices of the constraints.
+    pub constraints: Vec<usize>,
+}
+
+/// Formula that is changed in place by the search. Assignments are pushed on a trail and update the
+/// counters of the constraints the variable occurs in, [`TrailFormula::undo`] reverts a decision with
+/// everything it implied. Unlike [`PBFormula::get_sub_formula`] no clause is copied per decision.
+///
+/// Every conflict of the propagation is analyzed in the implication graph and its first unique implication
+/// point clause is learned. Learned constraints follow from the whole formula and propagate like the original
+/// ones, but components, residuals and with them cache keys only contain the original constraints.
+#[derive(Clone)]
+pub struct TrailFormula {
+    /// Variables of the formula the trail formula was built from.
+    variables: Vec<u32>,
+    /// Constraints of the formula followed by the learned constraints.
+    constraints: Vec<Constraint>,
+    /// Number of constraints of the formula, the indices of learned constraints start here.
+    original: usize,
+    /// Original constraints every variable occurs in, with its coefficient there.
+    occurrences: Vec<Vec<(usize, i64)>>,
+    /// Learned constraints every variable occurs in, with its coefficient there.
+    learned_occurrences: Vec<Vec<(usize, i64)>>,
+    values: Vec<Option<bool>>,
+    trail: Vec<u32>,
+    /// Trail length before every decision.
+    decisions: Vec<usize>,
+    /// Position of an assigned variable on the trail.
+    position: Vec<usize>,
+    /// Number of decisions when a variable was assigned.
+    level: Vec<usize>,
+    /// Constraint that implied an assigned variable, `None` for decisions.
+    antecedent: Vec<Option<usize>>,
+    /// Constraints to check for propagation.
+    queue: Vec<usize>,
+    /// Marks of the component search, a variable or constraint is visited if its mark equals `stamp`.
+    stamp: u32,
+    variable_mark: Vec<u32>,
+    constraint_mark: Vec<u32>,
+    /// Constraints learned by this formula, not counting those it was forked with.
+    learned: u64,
+    /// Number of times a learned constraint implied a literal or was falsified.
+    learned_uses: u64,
+}
+
+impl TrailFormula {
+    pub fn new(formula: &PBFormula) -> TrailFormula {
+        let size = formula.clauses.iter()
+            .flat_map(|c| c.literals.iter().map(|l| l.name))
+            .chain(formula.variables.iter().copied())
+            .max()
+            .map_or(0, |v| v as usize + 1);
+        let mut occurrences = vec![Vec::new(); size];
+        let mut constraints = Vec::with_capacity(formula.clauses.len());
+        for (i, clause) in formula.clauses.iter().enumerate() {
+            for l in &clause.literals {
+                occurrences[l.name as usize].push((i, l.factor));
+            }
+            constraints.push(Constraint {
+                literals: clause.literals.clone(),
+                degree: clause.rhs,
+                positive: clause.get_max_pos_imp(),
+                negative: clause.get_max_neg_imp(),
+                max_factor: clause.literals.iter().map(|l| l.factor.abs()).max().unwrap_or(0),
+            });
+        }
+        TrailFormula {
+            variables: formula.variables.clone(),
+            original: constraints.len(),
+            constraints,
+            occurrences,
+            learned_occurrences: vec![Vec::new(); size],
+            values: vec![None; size],
+            trail: Vec::new(),
+            decisions: Vec::new(),
+            position: vec![0; size],
+            level: vec![0; size],
+            antecedent: vec![None; size],
+            queue: Vec::new(),
+            stamp: 0,
+            variable_mark: vec![0; size],
+            constraint_mark: vec![0; formula.clauses.len()],
+            learned: 0,
+            learned_uses: 0,
+        }
+    }
+
+    /// Copy with the same assignment and learned constraints for another thread.
+    /// The copy only counts the constraints it learns itself, see [`TrailFormula::learned`].
+    pub fn fork(&self) -> TrailFormula {
+        let mut formula = self.clone();
+        formula.learned = 0;
+        formula
+    }
+
+    /// Number of constraints learned by this formula.
+    pub fn learned(&self) -> u64 {
+        self.learned
+    }
+
+    /// Number of times a learned constraint implied a literal or was falsified. A search that did not
+    /// change this counter found the same literals and conflicts as without learned constraints.
+    pub fn learned_uses(&self) -> u64 {
+        self.learned_uses
+    }
+
+    /// Assigns `variable` to `value` and unit propagates. Returns the implied literals, or on a conflict
+    /// the decisions it follows from, in which case the assignment is already undone. The conflict is
+    /// learned and propagates after the undo.
+    pub fn decide(&mut self, variable: u32, value: bool) -> Result<Vec<(u32, bool)>, Vec<u32>> {
+        let start = self.trail.len();
+        self.decisions.push(start);
+        self.assign(variable, value, None);
+        match self.propagate() {
+            Some(c) => {
+                let reason = self.reason(&[c]);
+                let clause = self.analyze(c);
+                self.undo();
+                if let Some(clause) = clause {
+                    self.learn(&clause);
+                }
+                Err(reason)
+            }
+            None => Ok(self.trail[start + 1..].iter().map(|v| (*v, self.values[*v as usize].unwrap())).collect())
+        }
+    }
+
+    /// Assigned variables of a constraint whose values lower its slack, assigned before `position`.
+    /// Together they imply everything the constraint implies at that position.
+    fn explanation(&self, c: usize, position: usize) -> Vec<u32> {
+        self.constraints[c].literals.iter()
+            .filter(|l| self.values[l.name as usize].is_some_and(|value| value != (l.factor > 0) && self.position[l.name as usize] < position))
+            .map(|l| l.name)
+            .collect()
+    }
+
+    /// First unique implication point clause of the falsified constraint `c`: the explanation of the conflict
+    /// is resolved with the antecedents of the literals of the last decision level until a single one is left.
+    /// Returns the literals of the clause, `None` if the conflict does not involve the last decision.
+    fn analyze(&mut self, c: usize) -> Option<Vec<(u32, bool)>> {
+        if self.learned >= MAX_LEARNED as u64 {
+            return None;
+        }
+        let level = self.decisions.len();
+        self.stamp += 1;
+        let stamp = self.stamp;
+        let mut clause = Vec::new();
+        let mut open = 0;
+        let mut pending = self.explanation(c, self.trail.len());
+        let mut index = self.trail.len();
+        loop {
+            for v in pending {
+                let v = v as usize;
+                if self.variable_mark[v] == stamp {
+                    continue;
+                }
+                self.variable_mark[v] = stamp;
+                if self.level[v] == level {
+                    open += 1;
+                } else {
+                    clause.push(v as u32);
+                }
+            }
+            if open == 0 {
+                return None;
+            }
+            // the last marked literal of the decision level
+            let v = loop {
+                index -= 1;
+                let v = self.trail[index] as usize;
+                if self.variable_mark[v] == stamp && self.level[v] == level {
+                    break v;
+                }
+            };
+            open -= 1;
+            if open == 0 {
+                clause.push(v as u32);
+                break;
+            }
+            pending = self.explanation(self.antecedent[v].expect("decisions are the first literal of their level"), self.position[v]);
+        }
+        Some(clause.into_iter().map(|v| (v, !self.values[v as usize].unwrap())).collect())
+    }
+
+    /// Adds the clause over the given literals as learned constraint and queues it for propagation.
+    fn learn(&mut self, clause: &[(u32, bool)]) {
+        let literals: Vec<Literal> = clause.iter().map(|(name, value)| Literal { name: *name, factor: if *value { 1 } else { -1 } }).collect();
+        let mut constraint = Constraint {
+            degree: 1 - literals.iter().filter(|l| l.factor < 0).count() as i64,
+            positive: 0,
+            negative: 0,
+            max_factor: 1,
+            literals: Vec::new(),
+        };
+        let c = self.constraints.len();
+        for l in &literals {
+            let v = l.name as usize;
+            match self.values[v] {
+                Some(value) => {
+                    if value {
+                        constraint.degree -= l.factor;
+                    }
+                }
+                None if l.factor > 0 => constraint.positive += l.factor,
+                None => constraint.negative += l.factor,
+            }
+            self.learned_occurrences[v].push((c, l.factor));
+        }
+        constraint.literals = literals;
+        self.constraints.push(constraint);
+        self.queue.push(c);
+        self.learned += 1;
+    }
+
+    /// Reverts the last decision and the literals it implied.
+    pub fn undo(&mut self) {
+        let start = self.decisions.pop().expect("no decision to undo");
+        while self.trail.len() > start {
+            let v = self.trail.pop().unwrap() as usize;
+            let value = self.values[v].take().unwrap();
+            for (c, factor) in self.occurrences[v].iter().chain(&self.learned_occurrences[v]) {
+                self.constraints[*c].update(*factor, value, -1);
+            }
+            self.antecedent[v] = None;
+        }
+    }
+
+    fn assign(&mut self, variable: u32, value: bool, antecedent: Option<usize>) {
+        let v = variable as usize;
+        self.values[v] = Some(value);
+        self.position[v] = self.trail.len();
+        self.level[v] = self.decisions.len();
+        self.antecedent[v] = antecedent;
+        self.trail.push(variable);
+        for (c, factor) in self.occurrences[v].iter().chain(&self.learned_occurrences[v]) {
+            self.constraints[*c].update(*factor, value, 1);
+            self.queue.push(*c);
+        }
+    }
+
+    /// Assigns every literal whose coefficient exceeds the slack of an unsatisfied constraint.
+    /// Returns the first falsified constraint.
+    fn propagate(&mut self) -> Option<usize> {
+        while let Some(c) = self.queue.pop() {
+            let constraint = &self.constraints[c];
+            if constraint.falsified() {
+                self.queue.clear();
+                if c >= self.original {
+                    self.learned_uses += 1;
+                }
+                return Some(c);
+            }
+            let slack = constraint.slack();
+            if constraint.satisfied() || constraint.max_factor <= slack {
+                continue;
+            }
+            if c >= self.original {
+                self.learned_uses += 1;
+            }
+            // assigning an implied literal leaves the slack unchanged, so all of them can be assigned at once
+            let implied: Vec<(u32, bool)> = constraint.literals.iter()
+                .filter(|l| self.values[l.name as usize].is_none() && l.factor.abs() > slack)
+                .map(|l| (l.name, l.factor > 0))
+                .collect();
+            for (v, value) in implied {
+                if self.values[v as usize].is_none() {
+                    self.assign(v, value, Some(c));
+                }
+            }
+        }
+        None
+    }
+
+    /// Decisions that derived the assigned literals of the constraints, found by following the
+    /// constraints that implied them back to the decisions.
+    pub fn reason(&self, constraints: &[usize]) -> Vec<u32> {
+        let mut stack: Vec<u32> = Vec::new();
+        for c in constraints {
+            stack.extend(self.constraints[*c].literals.iter().map(|l| l.name).filter(|v| self.values[*v as usize].is_some()));
+        }
+        let mut visited: HashSet<u32> = HashSet::new();
+        let mut reason = Vec::new();
+        while let Some(v) = stack.pop() {
+            if !visited.insert(v) {
+                continue;
+            }
+            match self.antecedent[v as usize] {
+                None => reason.push(v),
+                Some(c) => {
+                    let position = self.position[v as usize];
+                    stack.extend(self.constraints[c].literals.iter()
+                        .map(|l| l.name)
+                        .filter(|u| self.values[*u as usize].is_some() && self.position[*u as usize] < position));
+                }
+            }
+        }
+        reason.sort_unstable();
+        reason
+    }
+
+    /// Unassigned variables of the formula.
+    pub fn unassigned(&self) -> Vec<u32> {
+        self.variables.iter().copied().filter(|v| self.values[*v as usize].is_none()).collect()
+    }
+
+    /// Original constraints that are not satisfied by the current assignment.
+    pub fn active_constraints(&self) -> Vec<usize> {
+        (0..self.original).filter(|c| !self.constraints[*c].satisfied()).collect()
+    }
+
+    /// Splits the unassigned ones of `variables` into the components of the unsatisfied constraints
+    /// and the variables that occur in no unsatisfied constraint.
+    pub fn components(&mut self, variables: &[u32]) -> (Vec<Component>, Vec<u32>) {
+        self.stamp += 1;
+        let stamp = self.stamp;
+        let mut components = Vec::new();
+        let mut free = Vec::new();
+        let mut stack = Vec::new();
+        for v in variables {
+            if self.values[*v as usize].is_some() || self.variable_mark[*v as usize] == stamp {
+                continue;
+            }
+            let mut component = Component {
+                variables: Vec::new(),
+                constraints: Vec::new(),
+            };
+            self.variable_mark[*v as usize] = stamp;
+            stack.push(*v);
+            while let Some(u) = stack.pop() {
+                component.variables.push(u);
+                for (c, _) in &self.occurrences[u as usize] {
+                    if self.constraint_mark[*c] == stamp || self.constraints[*c].satisfied() {
+                        continue;
+                    }
+                    self.constraint_mark[*c] = stamp;
+                    component.constraints.push(*c);
+                    for l in &self.constraints[*c].literals {
+                        let w = l.name as usize;
+                        if self.values[w].is_none() && self.variable_mark[w] != stamp {
+                            self.variable_mark[w] = stamp;
+                            stack.push(l.name);
+                        }
+                    }
+                }
+            }
+            if component.constraints.is_empty() {
+                free.push(*v);
+            } else {
+                component.variables.sort_unstable();
+                component.constraints.sort_unstable();
+                components.push(component);
+            }
+        }
+        (components, free)
+    }
+
+    /// Unsatisfied constraints over the given unassigned variables, read in place from the trail formula.
+    /// The constraints have to be all unsatisfied original constraints the variables occur in, like those of
+    /// a [`Component`] or [`TrailFormula::active_constraints`] with [`TrailFormula::unassigned`].
+    pub fn residual<'a>(&'a self, variables: &'a [u32], constraints: &'a [usize]) -> Residual<'a> {
+        Residual {
+            formula: self,
+            variables,
+            constraints,
+        }
+    }
+}
+
+/// Subformula of a [`TrailFormula`] under its current assignment, used for the cache key and the decision
+/// heuristic. Degrees and literals are read from the counters of the trail formula instead of being copied.
+#[derive(Clone, Copy)]
+pub struct Residual<'a> {
+    formula: &'a TrailFormula,
+    /// Sorted unassigned variables, variables that occur in no constraint are free.
+    pub variables: &'a [u32],
+    /// Sorted indices of the unsatisfied constraints.
+    pub constraints: &'a [usize],
+}
+
+impl<'a> Residual<'a> {
+    /// Whether no constraint is left, every variable is free then.
+    pub fn is_empty(&self) -> bool {
+        self.constraints.is_empty()
+    }
+
+    /// Right side of the constraint minus the coefficients of its literals assigned true.
+    pub fn degree(&self, constraint: usize) -> i64 {
+        self.formula.constraints[constraint].degree
+    }
+
+    /// Unassigned literals of the constraint.
+    pub fn literals(&self, constraint: usize) -> impl Iterator<Item = &'a Literal> {
+        let values = &self.formula.values;
+        self.formula.constraints[constraint].literals.iter().filter(move |l| values[l.name as usize].is_none())
+    }
+
+    /// Unsatisfied constraints the variable occurs in, with its coefficient there, taken from the occurrence lists.
+    pub fn occurrences(&self, variable: u32) -> impl Iterator<Item = (usize, i64)> + 'a {
+        let constraints = &self.formula.constraints;
+        self.formula.occurrences[variable as usize].iter().copied().filter(move |(c, _)| !constraints[*c].satisfied())
+    }
+
+    /// Key of the subformula in the cache: the variables followed by the index and the degree of every
+    /// constraint. The unassigned literals of a constraint are the ones among the variables, so the key
+    /// determines the subformula without encoding its literals. Unlike [`PBFormula::cache_key`], equal
+    /// subformulas derived from different constraints get different keys.
+    pub fn cache_key(&self) -> Box<[i64]> {
+        let mut key = Vec::with_capacity(1 + self.variables.len() + 2 * self.constraints.len());
+        key.push(self.variables.len() as i64);
+        key.extend(self.variables.iter().map(|v| *v as i64));
+        for c in self.constraints {
+            key.push(*c as i64);
+            key.push(self.degree(*c));
+        }
+        key.into_boxed_slice()
+    }
+
+    /// Copy of the constraints as formula, only for searches that assign variables themselves.
+    pub fn to_formula(&self) -> PBFormula {
+        PBFormula {
+            variables: self.variables.to_vec(),
+            clauses: self.constraints.iter().map(|c| Clause {
+                rhs: self.degree(*c),
+                literals: self.literals(*c).cloned().collect(),
+                reason: Vec::new(),
+            }).collect(),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Uniform sampling of models from a compiled circuit.
+//!
+//! The count of every node of the [`Ddnnf`] is the count the search cached for its subformula. A sample
+//! walks down from the root, takes a branch of a decision with probability proportional to its count and
+//! samples the components of an AND node independently, which gives every model the same probability.
+
+use num_bigint::{BigUint, RandBigInt};
+use num_traits::Zero;
+use rand::Rng;
+use crate::solving::ddnnf::{Ddnnf, Node};
+use crate::solving::weights::Unweighted;
+
+pub struct Sampler<'a> {
+    ddnnf: &'a Ddnnf,
+    counts: Vec<BigUint>,
+    /// Samples only contain the variables below, the variables of the circuit by default. Set it to the
+    /// number of variables before preprocessing to leave out its auxiliary variables.
+    pub variables: u32,
+}
+
+impl<'a> Sampler<'a> {
+    /// Counts the models of every node that agree with the `assumptions`, `None` if the root has no such model.
+    pub fn new(ddnnf: &'a Ddnnf, assumptions: &[(u32, bool)]) -> Option<Sampler<'a>> {
+        let counts = ddnnf.values(&Unweighted, assumptions);
+        if counts[ddnnf.root].is_zero() {
+            return None;
+        }
+        Some(Sampler {
+            ddnnf,
+            counts,
+            variables: ddnnf.variables,
+        })
+    }
+
+    /// Number of models samples are drawn from.
+    pub fn count(&self) -> &BigUint {
+        &self.counts[self.ddnnf.root]
+    }
+
+    /// Draws a model uniformly at random, sorted by variable. Contains the variables of the circuit below `variables`.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<(u32, bool)> {
+        let mut model = Vec::new();
+        let mut stack = vec![self.ddnnf.root];
+        // only nodes with models are reached, the children of an AND node have disjoint variables
+        while let Some(i) = stack.pop() {
+            match &self.ddnnf.nodes[i] {
+                Node::True | Node::False => {}
+                Node::Literal(v, b) if *v < self.variables => model.push((*v, *b)),
+                Node::Literal(..) => {}
+                Node::And(children) => stack.extend(children),
+                Node::Or(_, children) => {
+                    let mut r = rng.gen_biguint_below(&self.counts[i]);
+                    for c in children {
+                        if r < self.counts[*c] {
+                            stack.push(*c);
+                            break;
+                        }
+                        r -= &self.counts[*c];
+                    }
+                }
+            }
+        }
+        model.sort_unstable();
+        model
+    }
+
+    /// Draws `k` independent samples.
+    pub fn samples<R: Rng + ?Sized>(&self, rng: &mut R, k: usize) -> Vec<Vec<(u32, bool)>> {
+        (0..k).map(|_| self.sample(rng)).collect()
+    }
+}
@@ -1,185 +1,339 @@
 use std::collections::{HashMap, HashSet};
-use std::hash::{DefaultHasher, Hash, Hasher};
-use crate::{parsing, solving};
-use crate::parsing::equation_ds::{Equation, File};
-use crate::solving::pb_ds::{new, Clause, Literal, PBFormula};
-
-pub fn count(formula: &PBFormula, n: u32, start_progress: u32, end_progress: u32, cache_count: &mut HashMap<u64, u128>) -> u128 {
-    let mut formula_cache_count: u32 = 0;
-    let mut map_result = cache_count.get(&calculate_hash(&formula));
-    match map_result {
-        Some(c) => {
-            return *c;
-        }
-        None => {
-            if(formula.clauses.len() == 0){
-                2_u128.pow(n)
-            }else if formula.contains_false_clause() {
-                0
-            }else{
-                //let l = formula.clauses.first().unwrap().literals.first().unwrap();
-                let l = get_next_variable(formula);
-                let f1 = formula.get_sub_formula(l, true);
-                let f2 = formula.get_sub_formula(l, false);
-
-                let progress_mid = start_progress + (end_progress - start_progress) / 2;
-                let c1 = count(&f1, n-1, start_progress, progress_mid, cache_count);
-                if(end_progress - start_progress >= 1){
-                    println!("{progress_mid} %");
-                }
-                let c2 = count(&f2, n-1, progress_mid, end_progress, cache_count);
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use num_traits::Zero;
+use crate::solving::cache::{Cache, ConcurrentCache};
+use crate::solving::heuristics::{Heuristic, Occurrence};
+use crate::solving::pb_ds::{merge_reasons, Component, PBFormula, Residual, TrailFormula};
+use crate::solving::weights::Weights;
 
-                let res = c1 + c2;
-                cache_count.insert(calculate_hash(&formula), res);
-                return res;
-            }
-        }
+/// Counters of the conflict analysis of a [`Solver`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// Decisions whose propagation falsified a constraint.
+    pub conflicts: u64,
+    /// First unique implication point clauses learned from the conflicts, see [`TrailFormula`].
+    pub learned: u64,
+    /// Decisions whose second branch was skipped because the conflict of the first branch did not depend on them.
+    pub backjumps: u64,
+}
+
+impl Display for SearchStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} conflicts, {} learned constraints, {} backjumps", self.conflicts, self.learned, self.backjumps)
     }
 }
 
-fn calculate_hash<T: Hash>(t: &T) -> u64 {
-    let mut s = DefaultHasher::new();
-    t.hash(&mut s);
-    s.finish()
+/// Result of counting a subformula. If the subformula is unsatisfiable the conflict contains the
+/// decisions it follows from, a zero count caused by zero weights has no conflict.
+type Counted<V> = (V, Option<Vec<u32>>);
+
+/// Range of the progress output of a subformula, `None` writes none.
+type Progress = Option<(u32, u32)>;
+
+pub struct Solver<W: Weights> {
+    pub weights: W,
+    /// Counts of already solved subformulas keyed on [`Residual::cache_key`].
+    pub cache: Cache<W::Value>,
+    /// Variables the count is projected on, `None` counts over all variables.
+    pub projection: Option<HashSet<u32>>,
+    /// Decision heuristic, the most frequent variable by default.
+    pub heuristic: Box<dyn Heuristic + Send + Sync>,
+    pub stats: SearchStats,
+    /// Cache of the other tasks of a [`crate::solving::parallel::ParallelSolver`], it only receives
+    /// counts no learned constraint contributed to.
+    pub(crate) shared: Option<Arc<ConcurrentCache<W::Value>>>,
 }
 
-pub fn count_disconnected_components(pb_formula: PBFormula, n: u32,  start_progress: u32, end_progress: u32, mut cache_count: &mut HashMap<u64, u128>) -> u128 {
-    let disconnected_formula = formula_to_disconnected_formula(pb_formula, n);
-    //println!("partitions: {}", disconnected_formula.partitions.len());
-    let mut res: u128 = 1;
-    if disconnected_formula.partitions.len() == 0 {
-        return 2_u128.pow(n);
+impl<W: Weights> Solver<W> {
+    /// Creates a solver whose cache uses at most about `cache_memory` bytes.
+    pub fn new(weights: W, cache_memory: usize) -> Solver<W> {
+        Solver {
+            weights,
+            cache: Cache::new(cache_memory),
+            projection: None,
+            heuristic: Box::new(Occurrence),
+            stats: SearchStats::default(),
+            shared: None,
+        }
     }
-    let progress = end_progress - start_progress;
-    let partition_progress = progress / disconnected_formula.partitions.len() as u32;
-    let mut i = 0;
-
-    for partition in disconnected_formula.partitions {
-        let tmp = count_dc(&partition.formula, partition.formula.n, start_progress + i*partition_progress, start_progress + (i+1)*partition_progress, &mut cache_count);
-        i += 1;
-        res *= tmp;
+
+    fn free(&self, variables: &[u32]) -> W::Value {
+        projected_free(&self.weights, self.projection.as_ref(), variables)
     }
-    res
-}
 
-fn get_next_variable(pbformula: &PBFormula) -> u32 {
-    match get_necessary_variable(pbformula){
-        Some(t) => return t,
-        None => {
-            let mut counter: HashMap<u32,u64> = HashMap::new();
-            for clause in &pbformula.clauses {
-                for literal in &clause.literals {
-                    let tmp_res = counter.get(&literal.name);
-                    match tmp_res {
-                        None => {
-                            counter.insert(literal.name, 1);
-                        },
-                        Some(v) => {
-                            counter.insert(literal.name, v + 1);
-                        }
-                    }
+    fn count_unprojected(&self, formula: &TrailFormula, residual: &Residual) -> Counted<W::Value> {
+        let res = count_unprojected(&self.weights, self.projection.as_ref(), residual);
+        let conflict = if res.is_zero() { Some(formula.reason(residual.constraints)) } else { None };
+        (res, conflict)
+    }
+
+    fn implied(&self, implied: &[(u32, bool)], count: W::Value) -> W::Value {
+        weigh_implied(&self.weights, self.projection.as_ref(), implied, count)
+    }
+
+    /// Counts of a subformula that need no decision, `None` if the solver has to branch.
+    fn count_leaf(&mut self, formula: &Residual, key: &[i64]) -> Option<Counted<W::Value>> {
+        let cached = self.cache.get(key).or_else(|| self.shared.as_ref()?.get(key));
+        if let Some(c) = cached {
+            // a cached zero may come from zero weights, so it is only a conflict if the formula is unsatisfiable
+            return Some((c, None));
+        }
+        if formula.is_empty() {
+            return Some((self.free(formula.variables), None));
+        }
+        None
+    }
+
+    /// Caches the count of a subformula, counts no learned constraint contributed to are shared with other tasks.
+    fn store(&mut self, key: Box<[i64]>, value: W::Value, learned: bool) {
+        match &self.shared {
+            Some(shared) if !learned => shared.insert(key, value),
+            _ => self.cache.insert(key, value),
+        }
+    }
+
+    /// Conflict of a decision whose two branches are both unsatisfiable, the decision itself is resolved away.
+    fn resolve(&mut self, variable: u32, c1: Option<Vec<u32>>, c2: Option<Vec<u32>>) -> Option<Vec<u32>> {
+        let mut reason = merge_reasons(&c1?, &c2?);
+        reason.retain(|v| *v != variable);
+        Some(reason)
+    }
+
+    /// Counts without component decomposition. The subformula of every decision is the whole formula
+    /// under the trail, so the constraints the trail formula learns never discard a model.
+    pub fn count(&mut self, formula: &PBFormula, progress: Progress) -> W::Value {
+        if formula.contains_false_clause() {
+            self.stats.conflicts += 1;
+            return W::Value::zero();
+        }
+        let mut trail_formula = TrailFormula::new(formula);
+        let res = self.count_plain(&mut trail_formula, progress).0;
+        self.stats.learned += trail_formula.learned();
+        res
+    }
+
+    pub(crate) fn count_plain(&mut self, formula: &mut TrailFormula, progress: Progress) -> Counted<W::Value> {
+        let whole = Component {
+            variables: formula.unassigned(),
+            constraints: formula.active_constraints(),
+        };
+        self.branch(formula, &whole, false, progress)
+    }
+
+    /// Assigns a decision, a conflict in the propagation is counted and returned as zero count.
+    fn decide(&mut self, formula: &mut TrailFormula, variable: u32, value: bool) -> Result<Vec<(u32, bool)>, Counted<W::Value>> {
+        formula.decide(variable, value).map_err(|reason| {
+            self.stats.conflicts += 1;
+            self.heuristic.conflict(&reason);
+            (W::Value::zero(), Some(reason))
+        })
+    }
+
+    /// Counts the components of every subformula independently.
+    ///
+    /// The learned constraints follow from the whole formula, so they may discard models of a component whose
+    /// sibling components are unsatisfiable. Such a count is wrong, but it is multiplied by the zero of the
+    /// sibling. As in sharpSAT, the cache entries inserted since the components were split are invalidated
+    /// once a component counts zero after a learned constraint was used.
+    pub fn count_disconnected_components(&mut self, pb_formula: PBFormula, progress: Progress) -> W::Value {
+        if pb_formula.contains_false_clause() {
+            self.stats.conflicts += 1;
+            return W::Value::zero();
+        }
+        let mut trail_formula = TrailFormula::new(&pb_formula);
+        let res = self.count_components(&mut trail_formula, &pb_formula.variables, progress).0;
+        self.stats.learned += trail_formula.learned();
+        res
+    }
+
+    /// Counts the unassigned ones of `variables`, split into the components of the unsatisfied constraints.
+    pub(crate) fn count_components(&mut self, formula: &mut TrailFormula, variables: &[u32], progress: Progress) -> Counted<W::Value> {
+        let (components, free) = formula.components(variables);
+        let time = self.cache.time();
+        let uses = formula.learned_uses();
+        let mut res = self.free(&free);
+
+        for (i, component) in components.iter().enumerate() {
+            let (tmp, conflict) = if self.projection.as_ref().is_some_and(|p| !component.variables.iter().any(|v| p.contains(v))) {
+                // components without projected variables only have to be satisfiable
+                if satisfiable(&formula.residual(&component.variables, &component.constraints).to_formula()) {
+                    continue;
                 }
-            }
-            let mut max_index: u32 = 0;
-            let mut max_value: u64 = 0;
-            for (k,v) in counter.iter() {
-                if v > &max_value {
-                    max_value = *v;
-                    max_index = *k;
+                (W::Value::zero(), Some(formula.reason(&component.constraints)))
+            } else {
+                let part = progress.map(|(start, end)| {
+                    let size = (end - start) / components.len() as u32;
+                    (start + i as u32 * size, start + (i as u32 + 1) * size)
+                });
+                self.branch(formula, component, true, part)
+            };
+            if tmp.is_zero() {
+                if formula.learned_uses() != uses {
+                    self.cache.invalidate_since(time);
                 }
+                return (tmp, conflict);
             }
-            max_index
+            res = res * tmp;
         }
+        (res, None)
     }
-}
 
-fn get_next_variable_for_best_partition(pbformula: &PBFormula, n: u32) -> u32 {
-    match get_necessary_variable(pbformula){
-        Some(t) => return t,
-        None => {
-            let mut best_index: u32 = u32::MAX;
-            let mut best_value: u64 = u64::MAX;
-
-            let mut variables: HashSet<&Literal> = HashSet::new();
-            for clause in & pbformula.clauses {
-                for literal in & clause.literals {
-                    variables.insert(literal);
-                }
+    /// Counts a subformula that is not cached yet by deciding a variable and counting both branches, the search
+    /// of both engines. With `decompose` the branches are split into components again and counts a learned
+    /// constraint contributed to stay in the cache of the task. Otherwise `component` is the whole formula under
+    /// the trail, whose models no learned constraint discards.
+    fn branch(&mut self, formula: &mut TrailFormula, component: &Component, decompose: bool, progress: Progress) -> Counted<W::Value> {
+        let residual = formula.residual(&component.variables, &component.constraints);
+        let key = residual.cache_key();
+        let uses = formula.learned_uses();
+        let learned = |formula: &TrailFormula| decompose && formula.learned_uses() != uses;
+        if let Some(res) = self.count_leaf(&residual, &key) {
+            return res;
+        }
+        let l = match self.heuristic.next_variable(&residual, self.projection.as_ref()) {
+            Some(l) => l,
+            None => {
+                let res = self.count_unprojected(formula, &residual);
+                self.store(key, res.0.clone(), learned(formula));
+                return res;
             }
+        };
 
-            for variable in variables {
-                let potential_new_formula = pbformula.get_sub_formula(variable.name, true);
-                let potential_new_partition = formula_to_disconnected_formula(potential_new_formula, n);
+        let (first, second) = split_progress(progress);
+        let (c1, conflict1) = self.count_decision(formula, component, decompose, l, true, first);
+        if let Some(reason) = conflict1.as_ref().filter(|r| !r.contains(&l)) {
+            // the conflict does not depend on l, so the other branch fails the same way
+            self.stats.backjumps += 1;
+            let reason = reason.clone();
+            self.store(key, W::Value::zero(), learned(formula));
+            return (W::Value::zero(), Some(reason));
+        }
 
-                let mut max_number_clauses = 0;
-                let mut max_number_clauses_index = 0;
-                for (i, partition) in potential_new_partition.partitions.iter().enumerate() {
-                    if partition.formula.clauses.len() > max_number_clauses {
-                        max_number_clauses = partition.formula.clauses.len();
-                        max_number_clauses_index = i;
-                    }
-                }
-                if max_number_clauses < best_value as usize {
-                    best_value = max_number_clauses as u64;
-                    best_index = variable.name;
-                }
+        report_progress(progress);
+        let (c2, conflict2) = self.count_decision(formula, component, decompose, l, false, second);
 
-            }
+        let res = self.weights.decision(l, true, c1) + self.weights.decision(l, false, c2);
+        let conflict = self.resolve(l, conflict1, conflict2);
+        self.store(key, res.clone(), learned(formula));
+        (res, conflict)
+    }
 
-            best_index
+    fn count_decision(&mut self, formula: &mut TrailFormula, component: &Component, decompose: bool, variable: u32, value: bool, progress: Progress) -> Counted<W::Value> {
+        let mut implied = match self.decide(formula, variable, value) {
+            Ok(implied) => implied,
+            Err(conflict) => return conflict,
+        };
+        // learned constraints may imply literals of other components, those are weighed there
+        implied.retain(|(v, _)| component.variables.binary_search(v).is_ok());
+        let (c, conflict) = if decompose {
+            self.count_components(formula, &component.variables, progress)
+        } else {
+            self.count_plain(formula, progress)
+        };
+        formula.undo();
+        (self.implied(&implied, c), conflict)
+    }
+}
+
+/// Progress ranges of the two branches of a decision, each gets one half of the range of the decision.
+fn split_progress(progress: Progress) -> (Progress, Progress) {
+    match progress {
+        Some((start, end)) => {
+            let mid = start + (end - start) / 2;
+            (Some((start, mid)), Some((mid, end)))
         }
+        None => (None, None),
     }
 }
 
-fn count_dc(formula: &PBFormula, n: u32,  start_progress: u32, end_progress: u32, cache_count: &mut HashMap<u64, u128>) -> u128 {
-    let mut formula_cache_count: u32 = 0;
-    let mut map_result = cache_count.get(&calculate_hash(&formula));
-    match map_result {
-        Some(c) => {
-            return *c;
+/// Writes the middle of the progress range to stderr once the first branch of a decision is counted.
+/// This is the only output of the search, without a progress range nothing is written.
+fn report_progress(progress: Progress) {
+    if let Some((start, end)) = progress.filter(|(start, end)| end > start) {
+        eprintln!("{} %", start + (end - start) / 2);
+    }
+}
+
+/// Weight of the unconstrained variables that belong to the projection.
+pub(crate) fn projected_free<W: Weights>(weights: &W, projection: Option<&HashSet<u32>>, variables: &[u32]) -> W::Value {
+    match projection {
+        Some(projection) => {
+            let projected: Vec<u32> = variables.iter().copied().filter(|v| projection.contains(v)).collect();
+            weights.free(&projected)
         }
-        None => {
-            if(formula.clauses.len() == 0){
-                2_u128.pow(n)
-            }else if formula.contains_false_clause() {
-                0
-            }else{
-                //let l = formula.clauses.first().unwrap().literals.first().unwrap().name;
-                //let l = get_next_variable_for_best_partition(formula, n);
-                let l = get_next_variable(formula);
-                let f1 = formula.get_sub_formula(l, true);
-                let f2 = formula.get_sub_formula(l, false);
-                let n = f1.n;
-
-                let progress_mid = start_progress + (end_progress - start_progress) / 2;
-                let c1 = count_disconnected_components(f1, n, start_progress, progress_mid, cache_count);
-
-                if(end_progress - start_progress >= 1){
-                    println!("{progress_mid} %");
-                }
-                let c2 = count_disconnected_components(f2, n, progress_mid, end_progress, cache_count);
+        None => weights.free(variables)
+    }
+}
 
-                let res = c1 + c2;
-                cache_count.insert(calculate_hash(&formula), res);
-                return res;
-            }
+/// Count of a formula whose constraints contain no projected variable, the remaining
+/// variables only matter for the existence of a model.
+pub(crate) fn count_unprojected<W: Weights>(weights: &W, projection: Option<&HashSet<u32>>, formula: &Residual) -> W::Value {
+    if formula.is_empty() || satisfiable(&formula.to_formula()) {
+        projected_free(weights, projection, formula.variables)
+    } else {
+        W::Value::zero()
+    }
+}
+
+/// Weighs a count with the literals implied by unit propagation, they have a single value in every model.
+pub(crate) fn weigh_implied<W: Weights>(weights: &W, projection: Option<&HashSet<u32>>, implied: &[(u32, bool)], count: W::Value) -> W::Value {
+    let mut res = count;
+    for (variable, value) in implied {
+        if projection.is_none_or(|p| p.contains(variable)) {
+            res = weights.decision(*variable, *value, res);
         }
     }
+    res
 }
 
-fn get_necessary_variable(formula: &PBFormula) -> Option<u32> {
-    let mut n = 0;
-    let mut new_formula = formula.clone();
-    for clause in &formula.clauses {
-        if clause.literals.len() == 1 {
-            return Some(clause.literals.get(0)?.name);
+/// Picks the most frequent variable of the projection as next decision variable.
+/// Returns `None` if no clause contains a projected variable.
+pub(crate) fn get_next_variable(pbformula: &PBFormula, projection: Option<&HashSet<u32>>) -> Option<u32> {
+    let mut counter: HashMap<u32,u64> = HashMap::new();
+    for clause in &pbformula.clauses {
+        for literal in &clause.literals {
+            if projection.is_some_and(|p| !p.contains(&literal.name)) {
+                continue;
+            }
+            let tmp_res = counter.get(&literal.name);
+            match tmp_res {
+                None => {
+                    counter.insert(literal.name, 1);
+                },
+                Some(v) => {
+                    counter.insert(literal.name, v + 1);
+                }
+            }
         }
     }
+    let mut max_index = None;
+    let mut max_value: u64 = 0;
+    for (k,v) in counter.iter() {
+        if v > &max_value {
+            max_value = *v;
+            max_index = Some(*k);
+        }
+    }
+    max_index
+}
 
-    None
+/// Decides whether the formula has a model.
+pub fn satisfiable(formula: &PBFormula) -> bool {
+    if formula.clauses.is_empty() {
+        true
+    } else if formula.contains_false_clause() {
+        false
+    } else {
+        let l = get_next_variable(formula, None).unwrap();
+        satisfiable(&formula.get_sub_formula(l, true).0) || satisfiable(&formula.get_sub_formula(l, false).0)
+    }
 }
 
-fn formula_to_disconnected_formula(formula: PBFormula, n: u32) -> DisconnectedFormula {
+/// Splits the formula into components without shared variables. With a projection, variables
+/// that occur in no clause are only kept if they are projected and every partition records
+/// whether it contains a projected variable.
+pub fn formula_to_disconnected_formula(formula: PBFormula, projection: Option<&HashSet<u32>>) -> DisconnectedFormula {
     let mut disconnected_formula = DisconnectedFormula {
         partitions: Vec::new(),
         variables: HashSet::new()
@@ -189,8 +343,9 @@ fn formula_to_disconnected_formula(formula: PBFormula, n: u32) -> DisconnectedFo
             let mut new_partition_set = Vec::new();
             let mut new_partition = Partition{
                 variables: HashSet::new(),
+                projected: true,
                 formula: PBFormula{
-                    n: 0,
+                    variables: Vec::new(),
                     clauses: Vec::new()
                 }
             };
@@ -212,14 +367,14 @@ fn formula_to_disconnected_formula(formula: PBFormula, n: u32) -> DisconnectedFo
             }
 
             new_partition.formula.clauses.push(clause);
-            new_partition.formula.n = new_partition.variables.len() as u32;
             new_partition_set.push(new_partition);
             disconnected_formula.partitions = new_partition_set;
         }else{
             let mut new_partition = Partition{
                 variables: HashSet::new(),
+                projected: true,
                 formula: PBFormula {
-                    n: 0,
+                    variables: Vec::new(),
                     clauses: Vec::new()
                 }
             };
@@ -227,47 +382,43 @@ fn formula_to_disconnected_formula(formula: PBFormula, n: u32) -> DisconnectedFo
                 new_partition.variables.insert(l.name);
                 disconnected_formula.variables.insert(l.name);
             }
-            new_partition.formula.n = new_partition.variables.len() as u32;
             new_partition.formula.clauses.push(clause.clone());
             disconnected_formula.partitions.push(new_partition);
         }
     }
 
-    let mut sum_n = 0;
-    for f in &disconnected_formula.partitions {
-        sum_n += f.formula.n;
+    for partition in &mut disconnected_formula.partitions {
+        partition.formula.variables = partition.variables.iter().copied().collect();
+        partition.formula.variables.sort_unstable();
+        partition.projected = match projection {
+            Some(projection) => partition.variables.iter().any(|v| projection.contains(v)),
+            None => true
+        };
     }
-    let number_unassigned = n - sum_n;
 
-    if number_unassigned > 0 {
+    let unassigned: Vec<u32> = formula.variables.iter().copied()
+        .filter(|v| !disconnected_formula.variables.contains(v))
+        .filter(|v| projection.is_none_or(|p| p.contains(v)))
+        .collect();
+    if !unassigned.is_empty() {
         let unassigned_formula = PBFormula{
-            n: number_unassigned,
+            variables: unassigned.clone(),
             clauses: Vec::new()
         };
-        disconnected_formula.partitions.push(Partition{formula: unassigned_formula, variables: HashSet::new()});
+        disconnected_formula.partitions.push(Partition{formula: unassigned_formula, variables: unassigned.into_iter().collect(), projected: true});
     }
 
-/*
-    println!("{} (", disconnected_formula.partitions.len());
-    for p in &disconnected_formula.partitions {
-        println!("\t{}", p.formula.clauses.len());
-    }
-    println!(")\n");
-*/
-
-
-
-    //println!("{}", disconnected_formula.partitions.len());
-
     disconnected_formula
 }
 
-struct Partition {
-    formula: PBFormula,
-    variables: HashSet<u32>
+pub struct Partition {
+    pub formula: PBFormula,
+    pub variables: HashSet<u32>,
+    /// Whether the partition contains a variable of the projection.
+    pub projected: bool
 }
 
-struct DisconnectedFormula {
-    partitions: Vec<Partition>,
-    variables: HashSet<u32>
+pub struct DisconnectedFormula {
+    pub partitions: Vec<Partition>,
+    pub variables: HashSet<u32>
 }
\ No newline at end of file
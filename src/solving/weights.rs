@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use num_bigint::{BigInt, BigUint};
+use num_rational::BigRational;
+use num_traits::{One, Zero};
+use crate::parsing::equation_ds::File;
+
+/// Weight function of a model count, the count of a formula is the sum over all models
+/// of the product of the weights of the literals in the model.
+pub trait Weights {
+    type Value: Clone + Debug + Display + Zero + One + HeapSize;
+
+    fn literal(&self, variable: u32, value: bool) -> Self::Value;
+
+    /// Weighs the count of the subformula after `variable` has been set to `value`.
+    fn decision(&self, variable: u32, value: bool, count: Self::Value) -> Self::Value {
+        self.literal(variable, value) * count
+    }
+
+    /// Combined weight of variables that occur in no constraint.
+    fn free(&self, variables: &[u32]) -> Self::Value {
+        let mut result = Self::Value::one();
+        for v in variables {
+            result = result * (self.literal(*v, true) + self.literal(*v, false));
+        }
+        result
+    }
+}
+
+impl<W: Weights> Weights for &W {
+    type Value = W::Value;
+
+    fn literal(&self, variable: u32, value: bool) -> W::Value {
+        (*self).literal(variable, value)
+    }
+
+    fn decision(&self, variable: u32, value: bool, count: W::Value) -> W::Value {
+        (*self).decision(variable, value, count)
+    }
+
+    fn free(&self, variables: &[u32]) -> W::Value {
+        (*self).free(variables)
+    }
+}
+
+/// Memory a count allocates on the heap, part of the memory estimate of the cache.
+pub trait HeapSize {
+    /// Bytes owned by the value besides its own size.
+    fn heap_size(&self) -> usize;
+}
+
+impl HeapSize for BigUint {
+    fn heap_size(&self) -> usize {
+        self.bits().div_ceil(64) as usize * 8
+    }
+}
+
+impl HeapSize for BigRational {
+    fn heap_size(&self) -> usize {
+        self.numer().magnitude().heap_size() + self.denom().magnitude().heap_size()
+    }
+}
+
+/// Every literal has weight 1, which gives the number of models.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Unweighted;
+
+impl Weights for Unweighted {
+    type Value = BigUint;
+
+    fn literal(&self, _variable: u32, _value: bool) -> BigUint {
+        BigUint::one()
+    }
+
+    fn decision(&self, _variable: u32, _value: bool, count: BigUint) -> BigUint {
+        count
+    }
+
+    fn free(&self, variables: &[u32]) -> BigUint {
+        BigUint::one() << variables.len()
+    }
+}
+
+/// Exact rational weights per literal, literals without a weight have weight 1.
+#[derive(Clone, Debug, Default)]
+pub struct LiteralWeights {
+    weights: HashMap<(u32, bool), BigRational>,
+}
+
+impl LiteralWeights {
+    pub fn new() -> LiteralWeights {
+        LiteralWeights::default()
+    }
+
+    /// Collects the weights declared in the file.
+    pub fn from_file(file: &File) -> LiteralWeights {
+        let mut weights = LiteralWeights::new();
+        for w in &file.weights {
+            weights.set(w.name, !w.negated, w.weight.clone());
+        }
+        weights
+    }
+
+    pub fn set(&mut self, variable: u32, value: bool, weight: BigRational) {
+        self.weights.insert((variable, value), weight);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+}
+
+impl Weights for LiteralWeights {
+    type Value = BigRational;
+
+    fn literal(&self, variable: u32, value: bool) -> BigRational {
+        match self.weights.get(&(variable, value)) {
+            Some(w) => w.clone(),
+            None => BigRational::one()
+        }
+    }
+
+    fn free(&self, variables: &[u32]) -> BigRational {
+        let mut unweighted = 0;
+        let mut result = BigRational::one();
+        for v in variables {
+            if self.weights.contains_key(&(*v, true)) || self.weights.contains_key(&(*v, false)) {
+                result *= self.literal(*v, true) + self.literal(*v, false);
+            } else {
+                unweighted += 1;
+            }
+        }
+        result * BigRational::from_integer(BigInt::one() << unweighted)
+    }
+}
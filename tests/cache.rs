@@ -0,0 +1,115 @@
+use parser_test::solving::cache::{Cache, CacheStats};
+use parser_test::solving::pb_ds::{Clause, Literal, PBFormula};
+use parser_test::solving::weights::HeapSize;
+use parser_test::{build_formula, count_weighted_with_stats, parse_str, preprocess, BigUint, CountOptions};
+use parser_test::solving::weights::Unweighted;
+
+#[test]
+fn statistics_count_hits_misses_and_entries() {
+    let mut cache: Cache<BigUint> = Cache::new(1 << 20);
+    cache.insert(vec![1, 2].into(), BigUint::from(3u32));
+    cache.insert(vec![1, 3].into(), BigUint::from(4u32));
+    assert_eq!(cache.get(&[1, 2]), Some(BigUint::from(3u32)));
+    assert_eq!(cache.get(&[2, 2]), None);
+    // replacing a value keeps the number of entries
+    cache.insert(vec![1, 3].into(), BigUint::from(5u32));
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.evictions, stats.entries), (1, 1, 0, 2));
+    assert!(stats.memory > 0);
+}
+
+#[test]
+fn memory_includes_the_heap_of_the_values() {
+    let mut small: Cache<BigUint> = Cache::new(1 << 20);
+    let mut large: Cache<BigUint> = Cache::new(1 << 20);
+    small.insert(vec![1].into(), BigUint::from(1u32));
+    let value: BigUint = BigUint::from(1u32) << 80_000;
+    assert!(value.heap_size() >= 10_000);
+    large.insert(vec![1].into(), value);
+    assert!(large.stats().memory >= small.stats().memory + 10_000);
+}
+
+#[test]
+fn least_recently_used_entries_are_evicted() {
+    let mut cache: Cache<BigUint> = Cache::new(20_000);
+    for i in 0..10 {
+        cache.insert(vec![i].into(), BigUint::from(1u32) << 16_000);
+    }
+    // every entry takes more than 2000 bytes, so the limit cannot hold all of them
+    let stats: CacheStats = cache.stats();
+    assert!(stats.evictions > 0);
+    assert!(stats.memory <= 20_000);
+    assert_eq!(stats.entries as u64 + stats.evictions, 10);
+    assert!(cache.get(&[9]).is_some());
+    assert!(cache.get(&[0]).is_none());
+}
+
+#[test]
+fn counts_survive_a_tiny_cache() {
+    let content = "+1 x1 +1 x2 +1 x3 +1 x4 >= 2 ;\n+1 x3 +1 x4 +1 x5 +1 x6 >= 2 ;\n+1 x5 +1 x6 +1 x7 +1 x1 >= 2 ;\n";
+    let formula = build_formula(&preprocess(parse_str(content).unwrap()));
+    let (expected, _) = count_weighted_with_stats(formula.clone(), Unweighted, &CountOptions::default());
+    let options = CountOptions { cache_memory: 256, ..CountOptions::default() };
+    let (count, stats) = count_weighted_with_stats(formula, Unweighted, &options);
+    assert_eq!(count, expected);
+    assert_eq!(count, BigUint::from(56u32));
+    assert!(stats.cache.evictions > 0);
+}
+
+#[test]
+fn entries_inserted_after_a_time_are_invalidated() {
+    let mut cache: Cache<BigUint> = Cache::new(1 << 20);
+    cache.insert(vec![1].into(), BigUint::from(1u32));
+    let time = cache.time();
+    cache.insert(vec![2].into(), BigUint::from(2u32));
+    cache.insert(vec![3].into(), BigUint::from(3u32));
+    cache.invalidate_since(time);
+    cache.insert(vec![4].into(), BigUint::from(4u32));
+    assert_eq!(cache.get(&[1]), Some(BigUint::from(1u32)));
+    assert_eq!(cache.get(&[2]), None);
+    assert_eq!(cache.get(&[3]), None);
+    assert_eq!(cache.get(&[4]), Some(BigUint::from(4u32)));
+    let stats = cache.stats();
+    assert_eq!((stats.invalidated, stats.entries), (2, 2));
+}
+
+fn clause(rhs: i64, literals: &[(u32, i64)]) -> Clause {
+    Clause {
+        rhs,
+        literals: literals.iter().map(|(name, factor)| Literal { name: *name, factor: *factor }).collect(),
+        reason: Vec::new(),
+    }
+}
+
+fn formula(variables: &[u32], clauses: Vec<Clause>) -> PBFormula {
+    PBFormula { variables: variables.to_vec(), clauses }
+}
+
+#[test]
+fn cache_keys_ignore_the_order_of_clauses_and_literals() {
+    let key = formula(&[0, 1, 2, 3], vec![clause(2, &[(0, 2), (1, 1), (2, 1)]), clause(0, &[(2, 1), (3, -1)])]).cache_key();
+    let mut reordered = formula(&[0, 1, 2, 3], vec![clause(0, &[(3, -1), (2, 1)]), clause(2, &[(2, 1), (0, 2), (1, 1)])]);
+    assert_eq!(reordered.cache_key(), key);
+    // duplicate clauses and the decisions a clause was derived from do not change the formula
+    reordered.clauses.push(clause(2, &[(1, 1), (2, 1), (0, 2)]));
+    reordered.clauses[0].reason = vec![4];
+    assert_eq!(reordered.cache_key(), key);
+}
+
+#[test]
+fn cache_keys_of_different_formulas_differ() {
+    let key = formula(&[0, 1, 2, 3], vec![clause(2, &[(0, 2), (1, 1), (2, 1)]), clause(0, &[(2, 1), (3, -1)])]).cache_key();
+    for other in [
+        // another degree, coefficient or variable
+        formula(&[0, 1, 2, 3], vec![clause(1, &[(0, 2), (1, 1), (2, 1)]), clause(0, &[(2, 1), (3, -1)])]),
+        formula(&[0, 1, 2, 3], vec![clause(2, &[(0, 1), (1, 1), (2, 1)]), clause(0, &[(2, 1), (3, -1)])]),
+        formula(&[0, 1, 2, 3], vec![clause(2, &[(0, 2), (1, 1), (3, 1)]), clause(0, &[(2, 1), (3, -1)])]),
+        // another free variable
+        formula(&[0, 1, 2, 4], vec![clause(2, &[(0, 2), (1, 1), (2, 1)]), clause(0, &[(2, 1), (3, -1)])]),
+        // a clause less, or the literals of both clauses in one
+        formula(&[0, 1, 2, 3], vec![clause(2, &[(0, 2), (1, 1), (2, 1)])]),
+        formula(&[0, 1, 2, 3], vec![clause(2, &[(0, 2), (1, 1), (2, 1), (2, 1), (3, -1)])]),
+    ] {
+        assert_ne!(other.cache_key(), key, "{:?}", other);
+    }
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use common::{brute_force, brute_force_weighted, solve, solve_weighted};
+use parser_test::encoding::cnf::CnfEncoding;
+use parser_test::{parse_str, BigRational, BigUint, CountOptions};
+
+const ENCODINGS: [CnfEncoding; 3] = [CnfEncoding::SequentialCounter, CnfEncoding::Totalizer, CnfEncoding::Bdd];
+
+const FORMULAS: [&str; 7] = [
+    "+1 x1 +1 x2 +1 x3 +1 x4 +1 x5 >= 3 ;\n",
+    "+1 x1 +1 x2 +1 x3 +1 x4 +1 x5 <= 2 ;\n+1 x1 +1 ~x3 >= 1 ;\n",
+    "+5 x1 +3 x2 +2 x3 +1 x4 +1 ~x5 >= 6 ;\n",
+    "+3 x1 -2 x2 +2 ~x3 -1 x4 = 1 ;\n+1 x2 +1 x5 >= 1 ;\n",
+    // x5 is declared but unused, the auxiliary variable of the product follows it
+    "* #variable= 5 #constraint= 2\n+2 x1 x2 +1 x3 +1 ~x4 >= 2 ;\n+1 x1 +1 x4 <= 1 ;\n",
+    "+1 x1 +1 x2 >= 1 ;\n+1 ~x1 >= 1 ;\n+1 ~x2 >= 1 ;\n",
+    "+4 x1 +4 x2 +4 x3 +3 x4 +3 x5 +3 x6 >= 10 ;\n+1 x1 +1 x4 = 1 ;\n",
+];
+
+#[test]
+fn encodings_keep_the_count() {
+    for encoding in ENCODINGS {
+        for content in FORMULAS {
+            let file = parse_str(content).unwrap();
+            let expected = brute_force(&file);
+            let cnf = parse_str(&file.to_dimacs_string(encoding)).unwrap();
+            // projected on the variables of the formula by the `c p show` line
+            assert_eq!(solve(cnf.clone(), &CountOptions::default()), expected, "{:?}\n{}", encoding, content);
+            // the auxiliary variables are defined by the original variables
+            let mut unprojected = cnf;
+            unprojected.projection = None;
+            assert_eq!(solve(unprojected, &CountOptions::default()), expected, "{:?} unprojected\n{}", encoding, content);
+        }
+    }
+}
+
+#[test]
+fn projection_is_written_as_show_line() {
+    let content = "* show x2 x4\n+1 x1 +1 x2 +1 x3 +1 x4 >= 2 ;\n";
+    let file = parse_str(content).unwrap();
+    for encoding in ENCODINGS {
+        let dimacs = file.to_dimacs_string(encoding);
+        assert!(dimacs.lines().any(|l| l == "c p show 2 4 0"), "{}", dimacs);
+        assert_eq!(solve(parse_str(&dimacs).unwrap(), &CountOptions::default()), BigUint::from(4u32));
+    }
+}
+
+#[test]
+fn weights_are_written_exactly() {
+    let content = "* weight x1 4/3\n* weight ~x1 0.25\n* weight x2 7\n* weight ~x3 1/7\n+2 x1 +1 x2 +1 x3 >= 2 ;\n";
+    let file = parse_str(content).unwrap();
+    let dimacs = file.to_dimacs_string(CnfEncoding::default());
+    for line in ["c p weight 1 4/3 0", "c p weight -1 0.25 0", "c p weight 2 7 0", "c p weight -3 1/7 0"] {
+        assert!(dimacs.lines().any(|l| l == line), "missing `{}` in\n{}", line, dimacs);
+    }
+    let cnf = parse_str(&dimacs).unwrap();
+    let weights: Vec<(u32, bool, BigRational)> = cnf.weights.iter().map(|w| (w.name, w.negated, w.weight.clone())).collect();
+    let expected: Vec<(u32, bool, BigRational)> = file.weights.iter().map(|w| (w.name, w.negated, w.weight.clone())).collect();
+    assert_eq!(weights, expected);
+    assert_eq!(solve_weighted(cnf, &CountOptions::default()), brute_force_weighted(&file));
+}
@@ -0,0 +1,97 @@
+// every test crate uses a different part of the helpers
+#![allow(dead_code)]
+
+use std::collections::HashSet;
+use num_traits::{One, Zero};
+use parser_test::parsing::equation_ds::{EquationPart, EquationSide, EquationSymbol, File};
+use parser_test::solving::weights::{LiteralWeights, Weights};
+use parser_test::{build_formula, count, count_weighted, preprocess, BigRational, BigUint, CountOptions};
+
+fn side_value(side: &EquationSide, assignment: u64) -> i64 {
+    let value = |name: u32, negated: bool| (assignment >> name & 1 == 1) != negated;
+    side.literals.iter().map(|part| match part {
+        EquationPart::Literal { factor, name, negated } => if value(*name, *negated) { *factor } else { 0 },
+        EquationPart::Product { factor, literals } => {
+            if literals.iter().all(|l| value(l.name, l.negated)) { *factor } else { 0 }
+        }
+        EquationPart::Factor(f) => *f,
+    }).sum()
+}
+
+/// Assignments to all variables of the file, as bit sets, that satisfy its equations.
+pub fn models(file: &File) -> Vec<u64> {
+    let variables = file.variable_count();
+    assert!(variables <= 20, "too many variables to brute force");
+    (0..1u64 << variables).filter(|assignment| file.equations.iter().all(|e| {
+        let (left, right) = (side_value(&e.e1, *assignment), side_value(&e.e2, *assignment));
+        match e.symbol {
+            EquationSymbol::eq => left == right,
+            EquationSymbol::ge => left >= right,
+            EquationSymbol::le => left <= right,
+        }
+    })).collect()
+}
+
+/// Number of models of the file, or of distinct assignments to its projection.
+pub fn brute_force(file: &File) -> BigUint {
+    let mask: u64 = match &file.projection {
+        Some(projection) => projection.iter().map(|v| 1 << v).sum(),
+        None => u64::MAX,
+    };
+    let projected: HashSet<u64> = models(file).into_iter().map(|m| m & mask).collect();
+    BigUint::from(projected.len())
+}
+
+/// Sum of the weights of the models of the file, ignoring its projection.
+pub fn brute_force_weighted(file: &File) -> BigRational {
+    let weights = LiteralWeights::from_file(file);
+    let mut result = BigRational::zero();
+    for m in models(file) {
+        let mut weight = BigRational::one();
+        for v in 0..file.variable_count() {
+            weight *= weights.literal(v, m >> v & 1 == 1);
+        }
+        result += weight;
+    }
+    result
+}
+
+/// Preprocesses and counts the file like `count --projected`.
+pub fn solve(file: File, options: &CountOptions) -> BigUint {
+    let options = CountOptions {
+        projection: file.projection.clone(),
+        ..options.clone()
+    };
+    count(build_formula(&preprocess(file)), &options)
+}
+
+/// Preprocesses and counts the file like `count --weighted`.
+pub fn solve_weighted(file: File, options: &CountOptions) -> BigRational {
+    let file = preprocess(file);
+    count_weighted(build_formula(&file), LiteralWeights::from_file(&file), options)
+}
+
+/// Random clauses over the variables, dense enough that many subformulas are unsatisfiable. The header
+/// declares all variables, so weight and show lines may name variables that occur in no clause.
+pub fn random_formula(seed: u64, variables: u64, weighted: bool) -> String {
+    let mut state = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    let mut next = |bound: u64| {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 33) % bound
+    };
+    let mut content = String::new();
+    if weighted {
+        for v in 1..=variables {
+            content += &format!("* weight x{} {}/10\n", v, 1 + next(9));
+        }
+    }
+    let constraints = variables * 3 / 2 + next(variables * 2);
+    for _ in 0..constraints {
+        for _ in 0..3 {
+            let negated = if next(2) == 0 { "~" } else { "" };
+            content += &format!("+{} {}x{} ", 1 + next(2), negated, 1 + next(variables));
+        }
+        content += &format!(">= {} ;\n", if next(5) == 0 { 2 } else { 1 });
+    }
+    format!("* #variable= {} #constraint= {}\n{}", variables, constraints, content)
+}
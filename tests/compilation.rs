@@ -0,0 +1,96 @@
+mod common;
+
+use std::collections::BTreeSet;
+use common::{brute_force, random_formula};
+use parser_test::{build_formula, compile, parse_str, preprocess, BigUint, CountOptions, Engine};
+
+/// Node of a written `.nnf` file with the variables below it.
+struct Node {
+    count: BigUint,
+    variables: BTreeSet<u32>,
+}
+
+/// Reads the `.nnf` text, checks the properties of a smooth d-DNNF and returns the model count over the
+/// variables of the header.
+fn evaluate(nnf: &str) -> BigUint {
+    let mut lines = nnf.lines();
+    let header: Vec<usize> = lines.next().unwrap().split(' ').skip(1).map(|n| n.parse().unwrap()).collect();
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut edges = 0;
+    for line in lines {
+        let tokens: Vec<&str> = line.split(' ').collect();
+        let numbers: Vec<i64> = tokens[1..].iter().map(|n| n.parse().unwrap()).collect();
+        let node = match tokens[0] {
+            "L" => {
+                let variable = numbers[0].unsigned_abs() as u32;
+                assert!(variable >= 1 && variable as usize <= header[2], "{}", line);
+                Node { count: BigUint::from(1u32), variables: [variable].into() }
+            }
+            "A" | "O" => {
+                let (count, children) = if tokens[0] == "A" { (numbers[0], &numbers[1..]) } else { (numbers[1], &numbers[2..]) };
+                assert_eq!(count as usize, children.len(), "{}", line);
+                assert!(children.iter().all(|c| (*c as usize) < nodes.len()), "children follow their node: {}", line);
+                edges += children.len();
+                let children: Vec<&Node> = children.iter().map(|c| &nodes[*c as usize]).collect();
+                let variables: BTreeSet<u32> = children.iter().flat_map(|c| c.variables.iter().copied()).collect();
+                if tokens[0] == "A" {
+                    assert_eq!(variables.len(), children.iter().map(|c| c.variables.len()).sum::<usize>(), "decomposable: {}", line);
+                    Node { count: children.iter().map(|c| c.count.clone()).product(), variables }
+                } else {
+                    assert!(children.iter().all(|c| c.variables == variables), "smooth: {}", line);
+                    Node { count: children.iter().map(|c| c.count.clone()).sum(), variables }
+                }
+            }
+            _ => panic!("unknown node {}", line),
+        };
+        nodes.push(node);
+    }
+    assert_eq!((nodes.len(), edges), (header[0], header[1]));
+    let root = nodes.last().unwrap();
+    // variables outside of the circuit are free
+    root.count.clone() << (header[2] - root.variables.len())
+}
+
+fn compiled(content: &str, options: &CountOptions) -> String {
+    let file = preprocess(parse_str(content).unwrap());
+    compile(build_formula(&file), &file, options).to_string()
+}
+
+#[test]
+fn written_circuits_count_the_models() {
+    for seed in 0..20 {
+        let content = random_formula(seed, 10, false);
+        let expected = brute_force(&parse_str(&content).unwrap());
+        for engine in [Engine::Plain, Engine::Components] {
+            let options = CountOptions { engine, ..CountOptions::default() };
+            assert_eq!(evaluate(&compiled(&content, &options)), expected, "seed {seed}, {engine:?}");
+        }
+    }
+}
+
+#[test]
+fn substituted_and_declared_variables_are_written() {
+    // x4 is substituted by x1, x5 is declared but unused and x1 x2 gets the auxiliary variable 6
+    let content = "* #variable= 5 #constraint= 2\n+1 x1 x2 +1 x3 >= 1 ;\n+1 x1 -1 x4 = 0 ;\n";
+    let nnf = compiled(content, &CountOptions::default());
+    assert!(nnf.starts_with("nnf ") && nnf.lines().next().unwrap().ends_with(" 6"), "{}", nnf);
+    assert!(nnf.lines().any(|l| l == "L 4" || l == "L -4"), "{}", nnf);
+    assert_eq!(evaluate(&nnf), BigUint::from(10u32));
+}
+
+#[test]
+fn unsatisfiable_formulas_compile_to_false() {
+    let nnf = compiled("+1 x1 +1 x2 >= 1 ;\n+1 ~x1 >= 1 ;\n+1 ~x2 >= 1 ;\n", &CountOptions::default());
+    assert_eq!(evaluate(&nnf), BigUint::from(0u32));
+}
+
+#[test]
+fn projected_circuits_only_contain_the_shown_variables() {
+    // x1 x2 = 00 needs x3 and ~x3, the other three assignments to x1 and x2 have a model
+    let content = "* show x1 x2\n+1 x1 +1 x3 >= 1 ;\n+1 x2 +1 ~x3 >= 1 ;\n";
+    let options = CountOptions { projection: Some(vec![0, 1]), ..CountOptions::default() };
+    let nnf = compiled(content, &options);
+    assert!(nnf.lines().filter_map(|l| l.strip_prefix("L ")).all(|l| ["1", "2"].contains(&l.trim_start_matches('-'))), "{}", nnf);
+    // the header counts all variables, x3 is not free but forgotten
+    assert_eq!(evaluate(&nnf) >> 1, BigUint::from(3u32));
+}
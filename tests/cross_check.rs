@@ -0,0 +1,61 @@
+mod common;
+
+use common::{brute_force, brute_force_weighted, random_formula, solve, solve_weighted};
+use parser_test::solving::ordering::EliminationHeuristic;
+use parser_test::{build_formula, count, parse_str, Branching, CountOptions, Engine};
+
+const BRANCHINGS: [Branching; 5] = [
+    Branching::Occurrence,
+    Branching::WeightedOccurrence,
+    Branching::Vsads,
+    Branching::BestPartition,
+    Branching::Static(EliminationHeuristic::MinFill),
+];
+
+/// Every combination of engine, branching and number of threads.
+fn configurations() -> Vec<CountOptions> {
+    let mut configurations = Vec::new();
+    for engine in [Engine::Plain, Engine::Components] {
+        for branching in BRANCHINGS {
+            for threads in [1, 4] {
+                configurations.push(CountOptions { engine, branching, threads, ..CountOptions::default() });
+            }
+        }
+    }
+    configurations
+}
+
+#[test]
+fn counts_match_brute_force() {
+    for seed in 0..20 {
+        let file = parse_str(&random_formula(seed, 12, false)).unwrap();
+        let expected = brute_force(&file);
+        // the formulas only contain `>=` constraints, so they can be counted without preprocessing
+        assert_eq!(count(build_formula(&file), &CountOptions::default()), expected, "seed {seed}");
+        for options in configurations() {
+            assert_eq!(solve(file.clone(), &options), expected, "seed {seed}, {options:?}");
+        }
+    }
+}
+
+#[test]
+fn weighted_counts_match_brute_force() {
+    for seed in 0..10 {
+        let file = parse_str(&random_formula(seed, 12, true)).unwrap();
+        let expected = brute_force_weighted(&file);
+        for options in configurations() {
+            assert_eq!(solve_weighted(file.clone(), &options), expected, "seed {seed}, {options:?}");
+        }
+    }
+}
+
+#[test]
+fn projected_counts_match_brute_force() {
+    for seed in 0..10 {
+        let file = parse_str(&format!("{}* show x1 x3 x5 x7 x9 x11\n", random_formula(seed, 12, false))).unwrap();
+        let expected = brute_force(&file);
+        for options in configurations() {
+            assert_eq!(solve(file.clone(), &options), expected, "seed {seed}, {options:?}");
+        }
+    }
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::{brute_force, solve, solve_weighted};
+use parser_test::parsing::dimacs::{self, is_dimacs};
+use parser_test::{parse_str, BigRational, BigUint, CountOptions};
+
+fn count(content: &str) -> BigUint {
+    solve(parse_str(content).unwrap(), &CountOptions::default())
+}
+
+#[test]
+fn header_selects_the_reader() {
+    assert!(is_dimacs("c a comment\n\np cnf 3 1\n1 -3 0\n"));
+    assert!(!is_dimacs("* #variable= 3 #constraint= 1\n+1 x1 +1 ~x3 >= 1 ;\n"));
+    assert!(!is_dimacs("+1 x1 >= 1 ;\n"));
+}
+
+#[test]
+fn clauses_are_counted() {
+    let content = "c a comment\np cnf 3 2\n1 -2 0\n2 3 0\n";
+    assert_eq!(count(content), brute_force(&parse_str(content).unwrap()));
+    assert_eq!(count(content), BigUint::from(4u32));
+    // x3 and x4 are declared but occur in no clause
+    assert_eq!(count("p cnf 4 1\n1 2 0\n"), BigUint::from(12u32));
+    // clauses may span lines and the last one may miss its 0
+    assert_eq!(count("p cnf 3 2\n1\n-2 0 2\n3"), BigUint::from(4u32));
+    // SATLIB files end with `%` and `0`
+    assert_eq!(count("p cnf 2 1\n1 2 0\n%\n0\n"), BigUint::from(3u32));
+}
+
+#[test]
+fn show_lines_project_the_count() {
+    let content = "p cnf 4 2\nc p show 1 2 0\n1 3 0\n2 -3 4 0\n";
+    let file = parse_str(content).unwrap();
+    assert_eq!(file.projection, Some(vec![0, 1]));
+    assert_eq!(solve(file.clone(), &CountOptions::default()), brute_force(&file));
+    assert_eq!(solve(file, &CountOptions::default()), BigUint::from(4u32));
+}
+
+#[test]
+fn weight_lines_weigh_literals() {
+    // the models x1 ~x2, ~x1 x2 and x1 x2 weigh 1/3, 1/4 and 1/12
+    let content = "p cnf 2 1\nc p weight 1 1/3 0\nc p weight -1 1 0\nc p weight 2 0.25 0\nc p weight -2 1 0\n1 2 0\n";
+    let expected = BigRational::new(2.into(), 3.into());
+    assert_eq!(solve_weighted(parse_str(content).unwrap(), &CountOptions::default()), expected);
+}
+
+#[test]
+fn errors_have_positions() {
+    for (content, line, column, token) in [
+        ("p cnf 2 1\n1 -3 0\n", 2, 3, "-3"),
+        ("p cnf 2 1\n1 x 0\n", 2, 3, "x"),
+        ("p cnf 2 1\np cnf 2 1\n1 0\n", 2, 1, "p"),
+        ("p cnf 2 1\nc p weight 1\n1 0\n", 2, 13, ""),
+        ("p cnf 2 abc\n1 0\n", 1, 9, "abc"),
+        ("p cnf 2 2\n1 0\n", 1, 9, "2"),
+        ("p cnf 2 1\n1 0\n2 0\n", 1, 9, "1"),
+        ("p cnf 2 1\nc p show 1 -2 0\n1 0\n", 2, 12, "-2"),
+    ] {
+        let error = parse_str(content).err().unwrap();
+        assert_eq!((error.line, error.column, error.token.as_str()), (line, column, token), "{}", content);
+    }
+    // files with a clause first are no DIMACS for `parse_str`
+    let error = dimacs::parse("1 2 0\np cnf 2 1\n").err().unwrap();
+    assert_eq!((error.line, error.column), (1, 1));
+    let error = dimacs::parse("c only a comment\n").err().unwrap();
+    assert_eq!(error.expected, ["`p cnf` header"]);
+}
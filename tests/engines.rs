@@ -0,0 +1,49 @@
+mod common;
+
+use common::{brute_force_weighted, solve, solve_weighted};
+use parser_test::{parse_str, BigUint, CountOptions, Engine};
+
+/// Three independent parts with 3, 2 and 5 models, one of them without constraints between its variables.
+const PARTS: &str = "+1 x1 +1 x2 +1 x3 >= 2 ;\n+1 ~x1 +1 x3 >= 1 ;\n+2 x4 +1 x5 +1 x6 = 2 ;\n+1 x7 x8 +1 ~x9 >= 1 ;\n";
+
+fn options(engine: Engine) -> CountOptions {
+    CountOptions { engine, ..CountOptions::default() }
+}
+
+#[test]
+fn disconnected_parts_are_counted_alike() {
+    for engine in [Engine::Plain, Engine::Components] {
+        assert_eq!(solve(parse_str(PARTS).unwrap(), &options(engine)), BigUint::from(30u32), "{engine:?}");
+    }
+}
+
+#[test]
+fn disconnected_parts_are_weighted_alike() {
+    let content = format!("* weight x1 1/2\n* weight ~x5 1/3\n* weight x9 4\n{}", PARTS);
+    let expected = brute_force_weighted(&parse_str(&content).unwrap());
+    for engine in [Engine::Plain, Engine::Components] {
+        assert_eq!(solve_weighted(parse_str(&content).unwrap(), &options(engine)), expected, "{engine:?}");
+    }
+}
+
+#[test]
+fn disconnected_parts_are_projected_alike() {
+    // the 3 models of the first part times the 2 values of x9
+    let content = format!("* show x1 x2 x3 x9\n{}", PARTS);
+    for engine in [Engine::Plain, Engine::Components] {
+        assert_eq!(solve(parse_str(&content).unwrap(), &options(engine)), BigUint::from(6u32), "{engine:?}");
+    }
+}
+
+#[test]
+fn counts_above_u128() {
+    // 198 of the 200 declared variables are free
+    let content = "* #variable= 200 #constraint= 1\n+1 x1 +1 x2 >= 1 ;\n";
+    let expected = BigUint::from(3u32) << 198;
+    for engine in [Engine::Plain, Engine::Components] {
+        for threads in [1, 4] {
+            let options = CountOptions { engine, threads, ..CountOptions::default() };
+            assert_eq!(solve(parse_str(content).unwrap(), &options), expected, "{engine:?}, {threads} threads");
+        }
+    }
+}
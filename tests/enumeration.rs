@@ -0,0 +1,102 @@
+mod common;
+
+use std::collections::HashSet;
+use common::{brute_force, random_formula, solve};
+use parser_test::solving::enumeration::Enumerator;
+use parser_test::{build_formula, parse_str, preprocess, BigUint, CountOptions};
+
+type Model = Vec<(u32, Option<bool>)>;
+
+/// Enumerates the file like `enumerate`, `projected` lists only its shown variables.
+fn enumerate(content: &str, projected: bool, dont_cares: bool, limit: Option<u64>) -> Vec<Model> {
+    let mut file = parse_str(content).unwrap();
+    file.name_declared_variables();
+    let variables = file.variable_count();
+    let shown = if projected { file.projection.clone() } else { None };
+    let file = preprocess(file);
+    let enumerator = Enumerator {
+        projection: shown.map(|s| s.into_iter().collect()),
+        expand_free: !dont_cares,
+        limit,
+        variables: Some(variables),
+        substitutions: file.substitutions.clone(),
+        ..Enumerator::default()
+    };
+    let mut models = Vec::new();
+    let reported = enumerator.enumerate(&build_formula(&file), |model| {
+        models.push(model.to_vec());
+        true
+    });
+    assert_eq!(reported, models.len() as u64);
+    models
+}
+
+/// Number of assignments the models stand for, a don't-care stands for both values.
+fn covered(models: &[Model]) -> BigUint {
+    models.iter().map(|m| BigUint::from(1u32) << m.iter().filter(|(_, b)| b.is_none()).count()).sum()
+}
+
+#[test]
+fn enumerated_models_match_the_count() {
+    for seed in 0..20 {
+        let content = random_formula(seed, 10, false);
+        let expected = brute_force(&parse_str(&content).unwrap());
+        let models = enumerate(&content, false, false, None);
+        assert_eq!(BigUint::from(models.len()), expected, "seed {seed}");
+        assert_eq!(models.iter().collect::<HashSet<_>>().len(), models.len(), "seed {seed}");
+        assert_eq!(covered(&enumerate(&content, false, true, None)), expected, "seed {seed}");
+    }
+}
+
+#[test]
+fn every_declared_and_only_original_variables_are_listed() {
+    // x5 and x6 are declared but unused, x1 x2 gets an auxiliary variable and x4 is substituted by x1
+    let content = "* #variable= 6 #constraint= 2\n+1 x1 x2 +1 x3 >= 1 ;\n+1 x1 -1 x4 = 0 ;\n";
+    let mut file = parse_str(content).unwrap();
+    file.name_declared_variables();
+    assert_eq!(file.variable_count(), 6);
+    let models = enumerate(content, false, false, None);
+    assert_eq!(models.len(), 20);
+    assert_eq!(BigUint::from(models.len()), solve(parse_str(content).unwrap(), &CountOptions::default()));
+    for model in &models {
+        assert_eq!(model.iter().map(|(v, _)| *v).collect::<Vec<_>>(), (0..6).collect::<Vec<_>>());
+        let value = |v: u32| model[v as usize].1.unwrap();
+        assert_eq!(value(0), value(3));
+        assert!(value(0) && value(1) || value(2));
+    }
+}
+
+#[test]
+fn declared_variables_get_free_names() {
+    let mut file = parse_str("* #variable= 4 #constraint= 1\n+1 x2 +1 x4 >= 1 ;\n").unwrap();
+    file.name_declared_variables();
+    let mut names: Vec<&str> = file.name_map.left_values().map(|n| n.as_str()).collect();
+    names.sort_unstable();
+    assert_eq!(names, ["x1", "x2", "x3", "x4"]);
+}
+
+#[test]
+fn limit_stops_the_enumeration() {
+    let content = random_formula(0, 10, false);
+    let all = enumerate(&content, false, false, None);
+    assert!(all.len() > 3);
+    let limited = enumerate(&content, false, false, Some(3));
+    assert_eq!(limited.len(), 3);
+    assert_eq!(limited.iter().collect::<HashSet<_>>().len(), 3);
+    assert!(limited.iter().all(|m| all.contains(m)));
+    assert!(enumerate(&content, false, false, Some(0)).is_empty());
+}
+
+#[test]
+fn projected_models_match_the_projected_count() {
+    for seed in 0..10 {
+        let content = format!("{}* show x1 x2 x3 x5 x8\n", random_formula(seed, 10, false));
+        let file = parse_str(&content).unwrap();
+        let expected = brute_force(&file);
+        let models = enumerate(&content, true, false, None);
+        assert_eq!(BigUint::from(models.len()), expected, "seed {seed}");
+        let shown = file.projection.unwrap();
+        assert!(models.iter().all(|m| m.len() == 5 && m.iter().all(|(v, _)| shown.contains(v))), "seed {seed}");
+        assert_eq!(covered(&enumerate(&content, true, true, None)), expected, "seed {seed}");
+    }
+}
@@ -0,0 +1,69 @@
+use std::process::{Command, Output};
+use parser_test::generating::generator::generate_pb_formula;
+use parser_test::parse_str;
+use parser_test::parsing::equation_ds::{EquationPart, EquationSymbol};
+
+#[test]
+fn coefficients_stay_within_the_largest_factor() {
+    let file = generate_pb_formula(40, 6, 4, 3).unwrap();
+    assert_eq!(file.equations.len(), 40);
+    assert!(file.variable_count() <= 6);
+    let mut factors = Vec::new();
+    for e in &file.equations {
+        assert!(e.symbol == EquationSymbol::ge);
+        assert_eq!(e.e1.literals.len(), 4);
+        for l in &e.e1.literals {
+            match l {
+                EquationPart::Literal { factor, .. } => factors.push(*factor),
+                _ => panic!("unexpected term {}", l),
+            }
+        }
+    }
+    assert!(factors.iter().all(|f| (1..=3).contains(f)));
+    // 160 coefficients drawn from 3 values
+    assert!(factors.contains(&3));
+}
+
+#[test]
+fn invalid_arguments_are_errors() {
+    assert!(generate_pb_formula(5, 0, 3, 1).is_err());
+    assert!(generate_pb_formula(5, 4, 0, 1).is_err());
+    assert!(generate_pb_formula(5, 4, 3, 0).is_err());
+    assert!(generate_pb_formula(0, 4, 3, 1).is_err());
+}
+
+#[test]
+fn the_largest_coefficients_are_accepted() {
+    // the coefficients of a constraint sum up to more than i32::MAX
+    let file = generate_pb_formula(3, 2, 4, i32::MAX).unwrap();
+    assert_eq!(file.equations.len(), 3);
+    assert!(file.equations.iter().all(|e| e.e1.literals.len() == 4));
+}
+
+fn generate(arguments: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_parserTest")).arg("generate").args(arguments).output().unwrap()
+}
+
+#[test]
+fn generate_writes_the_requested_formula() {
+    let output = generate(&["--equations", "7", "--variables", "5", "--size", "3", "--max-factor", "4"]);
+    assert!(output.status.success());
+    let file = parse_str(&String::from_utf8(output.stdout).unwrap()).unwrap();
+    assert_eq!(file.equations.len(), 7);
+    assert!(file.variable_count() <= 5);
+    for e in &file.equations {
+        assert_eq!(e.e1.literals.len(), 3);
+        assert!(e.e1.literals.iter().all(|l| matches!(l, EquationPart::Literal { factor: 1..=4, .. })), "{}", e.e1);
+    }
+    let output = generate(&["--equations", "2", "--variables", "3", "--to", "dimacs"]);
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("p cnf "));
+}
+
+#[test]
+fn generate_rejects_invalid_arguments() {
+    for arguments in [["--equations", "0"], ["--variables", "0"], ["--size", "0"], ["--max-factor", "0"], ["--equations", "-1"]] {
+        let output = generate(&arguments);
+        assert_eq!(output.status.code(), Some(2), "{:?}", arguments);
+        assert!(output.stdout.is_empty(), "{:?}", arguments);
+    }
+}
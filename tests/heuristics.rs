@@ -0,0 +1,69 @@
+mod common;
+
+use std::collections::HashSet;
+use common::solve;
+use parser_test::solving::heuristics::{BestPartition, Heuristic, Occurrence, Vsads, WeightedOccurrence};
+use parser_test::solving::pb_ds::TrailFormula;
+use parser_test::{build_formula, parse_str, BigUint, Branching, CountOptions, Engine};
+
+/// Decision of the heuristic on the whole formula, before any assignment.
+fn next(heuristic: &dyn Heuristic, content: &str, projection: Option<&HashSet<u32>>) -> Option<u32> {
+    let formula = TrailFormula::new(&build_formula(&parse_str(content).unwrap()));
+    let (variables, constraints) = (formula.unassigned(), formula.active_constraints());
+    heuristic.next_variable(&formula.residual(&variables, &constraints), projection)
+}
+
+#[test]
+fn occurrence_picks_the_most_frequent_variable() {
+    let content = "+1 x1 +1 x2 >= 1 ;\n+1 x2 +1 x3 >= 1 ;\n+1 ~x2 +1 x3 +1 x4 >= 1 ;\n";
+    assert_eq!(next(&Occurrence, content, None), Some(1));
+    let projection: HashSet<u32> = [0, 2].into();
+    assert_eq!(next(&Occurrence, content, Some(&projection)), Some(2));
+    assert_eq!(next(&Occurrence, content, Some(&HashSet::new())), None);
+}
+
+#[test]
+fn satisfied_constraints_are_not_counted() {
+    // x2 occurs most often, but deciding x1 satisfies three of its four constraints
+    let content = "+1 x1 +1 x2 >= 1 ;\n+1 x1 +1 x2 >= 1 ;\n+1 x1 +1 x2 >= 1 ;\n+1 x2 +1 x3 >= 1 ;\n+1 x3 +1 x4 >= 1 ;\n+1 x3 +1 x5 >= 1 ;\n";
+    assert_eq!(next(&Occurrence, content, None), Some(1));
+    let mut formula = TrailFormula::new(&build_formula(&parse_str(content).unwrap()));
+    formula.decide(0, true).unwrap();
+    let (variables, constraints) = (formula.unassigned(), formula.active_constraints());
+    assert_eq!(Occurrence.next_variable(&formula.residual(&variables, &constraints), None), Some(2));
+}
+
+#[test]
+fn weighted_occurrence_prefers_deciding_coefficients() {
+    // x2 occurs more often, but x1 decides the first constraint on its own
+    let content = "+3 x1 +1 x2 +1 x3 +1 x4 >= 3 ;\n+1 x2 +1 x3 +1 x4 +1 x5 >= 3 ;\n";
+    assert_eq!(next(&WeightedOccurrence, content, None), Some(0));
+}
+
+#[test]
+fn vsads_prefers_variables_of_recent_conflicts() {
+    let content = "+1 x1 +1 x2 >= 1 ;\n+1 x1 +1 x3 >= 1 ;\n+1 x1 +1 x3 >= 1 ;\n";
+    let vsads = Vsads::default();
+    assert_eq!(next(&vsads, content, None), Some(0));
+    vsads.conflict(&[1]);
+    vsads.conflict(&[1]);
+    assert_eq!(next(&vsads, content, None), Some(1));
+}
+
+#[test]
+fn best_partition_picks_the_separator() {
+    let content = "+1 x1 +1 x2 >= 1 ;\n+1 x1 +1 x3 >= 1 ;\n+1 x3 +1 x4 >= 1 ;\n+1 x4 +1 x5 >= 1 ;\n";
+    assert_eq!(next(&BestPartition, content, None), Some(2));
+}
+
+#[test]
+fn all_heuristics_count_alike() {
+    // x1 -> x3 leaves 3 models with two of x1, x2 and x3, all of them set x3, which excludes x4 and x5
+    let content = "+1 x1 +1 x2 +1 x3 >= 2 ;\n+1 ~x1 +1 x3 >= 1 ;\n+2 x3 +1 x4 +1 x5 = 2 ;\n";
+    for branching in [Branching::Occurrence, Branching::WeightedOccurrence, Branching::Vsads, Branching::BestPartition] {
+        for engine in [Engine::Plain, Engine::Components] {
+            let options = CountOptions { engine, branching, ..CountOptions::default() };
+            assert_eq!(solve(parse_str(content).unwrap(), &options), BigUint::from(3u32), "{branching:?}, {engine:?}");
+        }
+    }
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use common::{brute_force, solve};
+use num_traits::Zero;
+use parser_test::solving::pb_ds::TrailFormula;
+use parser_test::solving::weights::Unweighted;
+use parser_test::{build_formula, count_weighted_with_stats, parse_str, CountOptions, Engine};
+
+const ENGINES: [Engine; 2] = [Engine::Plain, Engine::Components];
+
+/// Three pigeons in two holes, `x(2p-1)` and `x(2p)` place pigeon `p` in the first or second hole.
+const PIGEONS: &str = "+1 x1 +1 x2 >= 1 ;\n+1 x3 +1 x4 >= 1 ;\n+1 x5 +1 x6 >= 1 ;\n\
+    +1 ~x1 +1 ~x3 >= 1 ;\n+1 ~x1 +1 ~x5 >= 1 ;\n+1 ~x3 +1 ~x5 >= 1 ;\n\
+    +1 ~x2 +1 ~x4 >= 1 ;\n+1 ~x2 +1 ~x6 >= 1 ;\n+1 ~x4 +1 ~x6 >= 1 ;\n";
+
+#[test]
+fn both_engines_learn_from_conflicts() {
+    let formula = build_formula(&parse_str(PIGEONS).unwrap());
+    for engine in ENGINES {
+        let options = CountOptions { engine, ..CountOptions::default() };
+        let (count, stats) = count_weighted_with_stats(formula.clone(), Unweighted, &options);
+        assert!(count.is_zero(), "{engine:?}");
+        assert!(stats.search.conflicts > 0, "{engine:?}");
+        assert!(stats.search.learned > 0, "{engine:?}");
+    }
+}
+
+#[test]
+fn decisions_outside_the_conflict_are_skipped() {
+    // x7 occurs most often and is decided first, but the pigeons fail without it
+    let content = format!("{PIGEONS}+1 x7 +1 x8 >= 1 ;\n+1 x7 +1 x9 >= 1 ;\n+1 x7 +1 x10 >= 1 ;\n+1 x7 +1 x11 >= 1 ;\n");
+    let options = CountOptions { engine: Engine::Plain, ..CountOptions::default() };
+    let (count, stats) = count_weighted_with_stats(build_formula(&parse_str(&content).unwrap()), Unweighted, &options);
+    assert!(count.is_zero());
+    assert_eq!(stats.search.backjumps, 1);
+}
+
+#[test]
+fn learning_keeps_the_count() {
+    // a third hole x7 for the last pigeon leaves models next to the conflicts of the full holes
+    let content = PIGEONS.replace("+1 x5 +1 x6 >= 1", "+1 x5 +1 x6 +1 x7 >= 1");
+    let file = parse_str(&content).unwrap();
+    let expected = brute_force(&file);
+    for engine in ENGINES {
+        let options = CountOptions { engine, ..CountOptions::default() };
+        assert_eq!(solve(file.clone(), &options), expected, "{engine:?}");
+    }
+}
+
+#[test]
+fn learned_clause_propagates_after_the_conflict() {
+    // x1 implies both x2 and ~x2, so deciding x1 fails and the unit clause ~x1 is learned
+    let content = "+1 ~x1 +1 x2 >= 1 ;\n+1 ~x1 +1 ~x2 >= 1 ;\n+1 x1 +1 x3 +1 x4 >= 1 ;\n";
+    let formula = build_formula(&parse_str(content).unwrap());
+    let mut trail = TrailFormula::new(&formula);
+    assert_eq!(trail.decide(0, true), Err(vec![0]));
+    assert_eq!(trail.learned(), 1);
+    let implied = trail.decide(3, false).unwrap();
+    assert!(implied.contains(&(0, false)));
+    assert!(trail.learned_uses() > 0);
+}
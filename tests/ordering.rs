@@ -0,0 +1,50 @@
+mod common;
+
+use common::solve;
+use parser_test::solving::ordering::{EliminationHeuristic, VariableOrder};
+use parser_test::{build_formula, parse_str, BigUint, Branching, CountOptions, Engine};
+
+const HEURISTICS: [EliminationHeuristic; 2] = [EliminationHeuristic::MinFill, EliminationHeuristic::MinDegree];
+
+fn order(content: &str, heuristic: EliminationHeuristic) -> VariableOrder {
+    VariableOrder::new(&build_formula(&parse_str(content).unwrap()), heuristic)
+}
+
+#[test]
+fn widths_of_simple_graphs() {
+    let path = "+1 x1 +1 x2 >= 1 ;\n+1 x2 +1 x3 >= 1 ;\n+1 x3 +1 x4 >= 1 ;\n+1 x4 +1 x5 >= 1 ;\n";
+    let cycle = format!("{}+1 x5 +1 x1 >= 1 ;\n", path);
+    let clique = "+1 x1 +1 x2 +1 x3 +1 x4 >= 2 ;\n";
+    for heuristic in HEURISTICS {
+        assert_eq!(order(path, heuristic).width, 1, "{heuristic:?}");
+        assert_eq!(order(&cycle, heuristic).width, 2, "{heuristic:?}");
+        assert_eq!(order(clique, heuristic).width, 3, "{heuristic:?}");
+    }
+}
+
+#[test]
+fn every_variable_of_a_clause_has_a_rank() {
+    let content = "* #variable= 6 #constraint= 3\n+1 x1 +1 x2 +1 x3 >= 1 ;\n+1 x3 +1 x4 >= 1 ;\n+1 x4 +1 ~x5 >= 1 ;\n";
+    for heuristic in HEURISTICS {
+        let order = order(content, heuristic);
+        let mut ranks: Vec<usize> = (0..5).map(|v| order.rank(v)).collect();
+        ranks.sort_unstable();
+        assert_eq!(ranks, [0, 1, 2, 3, 4], "{heuristic:?}");
+        // x6 is declared but occurs in no clause
+        assert_eq!(order.rank(5), usize::MAX, "{heuristic:?}");
+    }
+}
+
+#[test]
+fn static_orders_count_simple_graphs() {
+    // the clauses along a path of 5 variables have 13 models, the ones along a cycle 11
+    let path = "+1 x1 +1 x2 >= 1 ;\n+1 x2 +1 x3 >= 1 ;\n+1 x3 +1 x4 >= 1 ;\n+1 x4 +1 x5 >= 1 ;\n";
+    let cycle = format!("{}+1 x5 +1 x1 >= 1 ;\n", path);
+    for heuristic in HEURISTICS {
+        for engine in [Engine::Plain, Engine::Components] {
+            let options = CountOptions { engine, branching: Branching::Static(heuristic), ..CountOptions::default() };
+            assert_eq!(solve(parse_str(path).unwrap(), &options), BigUint::from(13u32), "{heuristic:?}, {engine:?}");
+            assert_eq!(solve(parse_str(&cycle).unwrap(), &options), BigUint::from(11u32), "{heuristic:?}, {engine:?}");
+        }
+    }
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::{brute_force, brute_force_weighted, random_formula, solve, solve_weighted};
+use parser_test::solving::weights::Unweighted;
+use parser_test::{build_formula, count_weighted_with_stats, parse_str, preprocess, BigUint, CountOptions, Engine};
+
+const ENGINES: [Engine; 2] = [Engine::Plain, Engine::Components];
+
+#[test]
+fn independent_components_are_counted_in_parallel() {
+    // six components with 3 models each
+    let content: String = (0..6).map(|i| format!("+1 x{} +1 x{} >= 1 ;\n", 2 * i + 1, 2 * i + 2)).collect();
+    for engine in ENGINES {
+        for threads in [2, 4, 8] {
+            let options = CountOptions { engine, threads, ..CountOptions::default() };
+            assert_eq!(solve(parse_str(&content).unwrap(), &options), BigUint::from(729u32), "{engine:?}, {threads} threads");
+        }
+    }
+}
+
+#[test]
+fn branches_are_counted_in_parallel() {
+    // at least 3 of 8 variables, a single component the threads split by decisions
+    let content = "+1 x1 +1 x2 +1 x3 +1 x4 +1 x5 +1 x6 +1 x7 +1 x8 >= 3 ;\n+1 x1 +1 ~x2 >= 1 ;\n";
+    let weighted = format!("* weight x1 1/2\n* weight ~x2 3\n{}", content);
+    let expected = brute_force(&parse_str(content).unwrap());
+    let expected_weighted = brute_force_weighted(&parse_str(&weighted).unwrap());
+    for engine in ENGINES {
+        for threads in [2, 4, 8] {
+            let options = CountOptions { engine, threads, ..CountOptions::default() };
+            assert_eq!(solve(parse_str(content).unwrap(), &options), expected, "{engine:?}, {threads} threads");
+            assert_eq!(solve_weighted(parse_str(&weighted).unwrap(), &options), expected_weighted, "{engine:?}, {threads} threads");
+        }
+    }
+}
+
+#[test]
+fn projected_branches_are_counted_in_parallel() {
+    // x1 + x2 >= 1 leaves 3 assignments of the shown variables, x3 to x6 are hidden
+    let content = "* show x1 x2\n+1 x1 +1 x2 +1 x3 >= 2 ;\n+1 x3 +1 x4 +1 x5 +1 x6 >= 2 ;\n+1 x1 +1 x2 >= 1 ;\n";
+    for engine in ENGINES {
+        let options = CountOptions { engine, threads: 4, ..CountOptions::default() };
+        assert_eq!(solve(parse_str(content).unwrap(), &options), BigUint::from(3u32), "{engine:?}");
+    }
+}
+
+#[test]
+fn tasks_below_the_split_depth_learn() {
+    // 40 variables need more decisions than the parallel solver splits on
+    let formula = build_formula(&preprocess(parse_str(&random_formula(0, 40, false)).unwrap()));
+    let (expected, _) = count_weighted_with_stats(formula.clone(), Unweighted, &CountOptions::default());
+    for engine in ENGINES {
+        let options = CountOptions { engine, threads: 4, ..CountOptions::default() };
+        let (count, stats) = count_weighted_with_stats(formula.clone(), Unweighted, &options);
+        assert_eq!(count, expected, "{engine:?}");
+        assert!(stats.search.learned > 0, "{engine:?}");
+        assert!(stats.cache.hits > 0, "{engine:?}");
+    }
+}
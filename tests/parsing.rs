@@ -0,0 +1,121 @@
+use parser_test::parsing::equation_ds::{EquationPart, ObjectiveSense};
+use parser_test::{build_formula, count, parse_str, preprocess, BigUint, CountOptions, Engine};
+
+#[test]
+fn syntax_errors_have_positions() {
+    let error = parse_str("+1 x1 +1 x2 >= 1 ;\n+1 x1 +1 >= ;\n").err().unwrap();
+    assert_eq!((error.line, error.column), (2, 13));
+    assert_eq!(error.line_content, "+1 x1 +1 >= ;");
+    assert_eq!(error.token, ";");
+    assert_eq!(error.expected, ["equation side"]);
+}
+
+#[test]
+fn errors_at_the_end_of_a_line_name_it() {
+    let error = parse_str("+1 x1 +1 x2 >=\r\n+1 x1 >= 1 ;\n").err().unwrap();
+    assert_eq!((error.line, error.column), (1, 15));
+    assert_eq!(error.line_content, "+1 x1 +1 x2 >=");
+    assert_eq!(error.message, "unexpected end of line, expected equation side");
+    let error = parse_str("+1 x1 +1 x2 >=").err().unwrap();
+    assert_eq!(error.message, "unexpected end of input, expected equation side");
+}
+
+#[test]
+fn numbers_out_of_range_are_rejected() {
+    let error = parse_str("+1 x1 >= 1 ;\n+3000000000 x1 >= 1 ;\n").err().unwrap();
+    assert_eq!((error.line, error.column), (2, 2));
+    assert_eq!(error.token, "3000000000");
+    assert!(error.message.contains("2147483647"), "{}", error.message);
+    let error = parse_str("+1 x1 >= 3000000000 ;\n").err().unwrap();
+    assert_eq!((error.line, error.column), (1, 10));
+}
+
+#[test]
+fn constraints_with_large_sums_are_counted() {
+    // every number fits in i32, but their sums, slacks and merged coefficients do not
+    for (content, expected) in [
+        ("+2000000000 x1 +2000000000 x2 +1 x3 >= 2000000000 ;\n", 6u32),
+        ("+2000000000 x1 +2000000000 x1 >= 1 ;\n", 1),
+        ("+2000000000 x1 +2000000000 x2 +2000000000 x3 >= 2100000000 ;\n", 4),
+        ("+2147483647 x1 +2147483647 x2 = 2147483647 ;\n", 2),
+        ("-2147483647 x1 -2147483647 x2 -2147483647 x3 >= -2147483647 ;\n", 4),
+    ] {
+        for engine in [Engine::Plain, Engine::Components] {
+            let options = CountOptions { engine, ..CountOptions::default() };
+            let file = preprocess(parse_str(content).unwrap());
+            assert_eq!(count(build_formula(&file), &options), BigUint::from(expected), "{content}");
+        }
+    }
+}
+
+#[test]
+fn show_and_weight_lines_reject_unknown_names() {
+    for (content, column) in [
+        ("* show x3\n+1 x1 +1 x2 >= 1 ;\n", 8),
+        ("* show x1 x3\n+1 x1 +1 x2 >= 1 ;\n", 11),
+        ("* weight x3 1/2\n+1 x1 +1 x2 >= 1 ;\n", 10),
+    ] {
+        let error = parse_str(content).err().unwrap();
+        assert_eq!((error.line, error.column, error.token.as_str()), (1, column, "x3"), "{}", content);
+        assert_eq!(error.message, "unknown variable `x3`");
+    }
+    // x3 is declared by the header but occurs in no constraint
+    let file = parse_str("* #variable= 3 #constraint= 1\n* show x3\n+1 x1 +1 x2 >= 1 ;\n").unwrap();
+    assert_eq!(file.projection, Some(vec![2]));
+}
+
+#[test]
+fn header_declares_unused_variables() {
+    let file = parse_str("* #variable= 4 #constraint= 1\n+1 x1 +1 x2 >= 1 ;\n").unwrap();
+    assert_eq!(file.declared_variables, Some(4));
+    assert_eq!(file.variable_count(), 4);
+    // x3 and x4 occur in no constraint and are free
+    assert_eq!(count(build_formula(&preprocess(file)), &CountOptions::default()), BigUint::from(12u32));
+    // the header may carry further counts
+    let file = parse_str("* #variable= 3 #constraint= 1 #product= 0\n+1 x1 >= 1 ;\n").unwrap();
+    assert_eq!(file.variable_count(), 3);
+}
+
+#[test]
+fn objectives_are_kept_but_not_counted() {
+    for (content, sense) in [
+        ("min: +1 x1 -2 x2 ;\n+1 x1 +1 x2 >= 1 ;\n", ObjectiveSense::Minimize),
+        ("* a comment\nmax: +3 x2 ;\n+1 x1 +1 x2 >= 1 ;\n", ObjectiveSense::Maximize),
+        ("* #variable= 2 #constraint= 1\nmin: ;\n+1 x1 +1 x2 >= 1 ;\n", ObjectiveSense::Minimize),
+    ] {
+        let file = parse_str(content).unwrap();
+        assert!(file.objective.as_ref().is_some_and(|o| o.sense == sense), "{}", content);
+        assert_eq!(file.equations.len(), 1, "{}", content);
+        assert_eq!(count(build_formula(&preprocess(file)), &CountOptions::default()), BigUint::from(3u32), "{}", content);
+    }
+}
+
+#[test]
+fn negated_literals_are_parsed() {
+    let file = parse_str("+1 ~x1 +2 ~x2 >= 2 ;\n").unwrap();
+    let negated: Vec<bool> = file.equations[0].e1.literals.iter().map(|l| matches!(l, EquationPart::Literal { negated: true, .. })).collect();
+    assert_eq!(negated, [true, true]);
+    // ~x2 holds in every model
+    assert_eq!(count(build_formula(&preprocess(file)), &CountOptions::default()), BigUint::from(2u32));
+}
+
+#[test]
+fn objectives_and_headers_out_of_place_are_rejected() {
+    let error = parse_str("+1 x1 +1 x2 >= 1 ;\nmin: +1 x1 ;\n").err().unwrap();
+    assert_eq!((error.line, error.token.as_str()), (2, ":"));
+    let error = parse_str("+1 x1 >= 1 ;\n* #variable= 1 #constraint= 1\n").err().unwrap();
+    assert_eq!((error.line, error.column), (2, 1));
+}
+
+#[test]
+fn malformed_headers_are_rejected() {
+    for (content, column, expected) in [
+        ("* #variable= x #constraint= 1\n+1 x1 >= 1 ;\n", 14, "number"),
+        ("* #variable= 2 #constraint= -1\n+1 x1 >= 1 ;\n", 29, "number"),
+        ("* #variable= 2\n+1 x1 >= 1 ;\n", 15, "`#constraint=`"),
+    ] {
+        let error = parse_str(content).err().unwrap();
+        assert_eq!((error.line, error.column), (1, column), "{}", content);
+        assert_eq!(error.expected, [expected], "{}", content);
+    }
+}
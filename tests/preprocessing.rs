@@ -0,0 +1,133 @@
+mod common;
+
+use common::{brute_force, brute_force_weighted, solve, solve_weighted};
+use parser_test::parsing::equation_ds::File;
+use parser_test::{build_formula, count, parse_str, preprocess, preprocess_with_report, BigUint, CountOptions};
+
+/// Written constraints of the file, without the header line.
+fn constraints(file: &File) -> Vec<String> {
+    file.to_string().lines().filter(|l| !l.starts_with('*')).map(str::to_string).collect()
+}
+
+#[test]
+fn auxiliary_variables_follow_declared_variables() {
+    // x4 is declared but unused, the auxiliary variable of x1 x2 must not take its index
+    let file = preprocess(parse_str("* #variable= 4 #constraint= 1\n+1 x1 x2 +1 x3 >= 1 ;\n").unwrap());
+    let auxiliary: Vec<u32> = file.name_map.iter().filter(|(name, _)| name.starts_with("aux")).map(|(_, v)| *v).collect();
+    assert_eq!(auxiliary, vec![4]);
+    assert_eq!(file.variable_count(), 5);
+    assert_eq!(count(build_formula(&file), &CountOptions::default()), BigUint::from(10u32));
+}
+
+#[test]
+fn normalization_keeps_the_count() {
+    for content in [
+        // negative coefficients become negated literals
+        "+4 x1 -2 x2 +6 x3 >= 4 ;\n+3 x1 +9 x4 >= 3 ;\n",
+        // saturation caps the coefficients at the degree
+        "+7 x1 +2 x2 +1 x3 >= 2 ;\n",
+        "-3 x1 -3 ~x2 +2 x3 <= -1 ;\n+2 x1 +4 x3 = 4 ;\n",
+        "+6 x1 +4 x2 +2 ~x3 >= 5 ;\n+1 x1 +1 x2 +1 x3 +1 x4 <= 2 ;\n",
+        "+2 x1 +2 x2 = 3 ;\n",
+    ] {
+        let file = parse_str(content).unwrap();
+        let expected = brute_force(&file);
+        assert_eq!(count(build_formula(&preprocess(file)), &CountOptions::default()), expected, "{}", content);
+    }
+}
+
+#[test]
+fn coefficients_are_positive_saturated_and_divided() {
+    let file = preprocess(parse_str("+4 x1 -2 x2 +6 x3 >= 4 ;\n+3 x1 +9 x4 >= 3 ;\n").unwrap());
+    assert_eq!(constraints(&file), ["+2*x1 +1*~x2 +3*x3 >= +3", "+1*x1 +1*x4 >= +1"]);
+}
+
+#[test]
+fn saturation_caps_coefficients_at_the_degree() {
+    let file = preprocess(parse_str("+7 x1 +2 x2 +1 x3 >= 2 ;\n").unwrap());
+    assert_eq!(constraints(&file), ["+2*x1 +2*x2 +1*x3 >= +2"]);
+}
+
+#[test]
+fn division_by_the_gcd_rounds_the_degree_up() {
+    let file = preprocess(parse_str("+4 x1 +4 x2 +4 x3 >= 5 ;\n").unwrap());
+    assert_eq!(constraints(&file), ["+1*x1 +1*x2 +1*x3 >= +2"]);
+    // saturating 5 to 3 leaves the GCD 3
+    let file = preprocess(parse_str("+5 x1 +3 x2 +3 x3 >= 3 ;\n").unwrap());
+    assert_eq!(constraints(&file), ["+1*x1 +1*x2 +1*x3 >= +1"]);
+}
+
+#[test]
+fn removed_constraints_keep_the_count() {
+    let content = "+2 x1 +2 x2 >= 2 ;\n+1 x1 +1 x2 >= 1 ;\n+1 x3 +1 ~x3 >= 1 ;\n+1 x1 +1 x2 >= 1 ;\n+1 x2 +1 x3 +1 x4 >= 2 ;\n+2 x2 +2 x3 +1 x4 >= 2 ;\n";
+    let file = parse_str(content).unwrap();
+    let expected = brute_force(&file);
+    let (file, report) = preprocess_with_report(file);
+    assert_eq!((report.satisfied, report.duplicates, report.subsumed), (1, 2, 1));
+    assert_eq!(file.equations.len(), 2);
+    assert_eq!(count(build_formula(&file), &CountOptions::default()), expected);
+}
+
+#[test]
+fn stronger_constraints_subsume_weaker_ones_over_the_same_literals() {
+    let (file, report) = preprocess_with_report(parse_str("+2 x1 +1 x2 +1 x3 >= 2 ;\n+1 x1 +1 x2 +1 x3 >= 2 ;\n").unwrap());
+    assert_eq!(report.subsumed, 1);
+    assert_eq!(constraints(&file), ["+1*x1 +1*x2 +1*x3 >= +2"]);
+    // x1 + x2 >= 1 implies x1 + x2 + x3 >= 1, but only constraints over the same literals are compared
+    let (file, report) = preprocess_with_report(parse_str("+1 x1 +1 x2 +1 x3 >= 1 ;\n+1 x1 +1 x2 >= 1 ;\n").unwrap());
+    assert_eq!((report.subsumed, file.equations.len()), (0, 2));
+}
+
+#[test]
+fn eliminated_variables_keep_the_count() {
+    // x1 is fixed, which fixes x2, x3 is equivalent to x4 and complementary to x5, which leaves x6 fixed
+    let content = "* weight x3 1/3\n* weight x5 3\n* weight ~x5 1/2\n+1 x1 >= 1 ;\n+1 ~x1 +1 ~x2 >= 1 ;\n+1 x3 -1 x4 = 0 ;\n+1 x3 +1 x5 = 1 ;\n+1 x4 +1 x5 +1 x6 +1 x2 >= 2 ;\n";
+    let file = parse_str(content).unwrap();
+    let (expected, expected_weighted) = (brute_force(&file), brute_force_weighted(&file));
+    let (preprocessed, report) = preprocess_with_report(file.clone());
+    assert_eq!((report.fixed, report.substituted), (3, 2));
+    assert_eq!(count(build_formula(&preprocessed), &CountOptions::default()), expected);
+    assert_eq!(solve_weighted(file, &CountOptions::default()), expected_weighted);
+}
+
+#[test]
+fn eliminated_variables_keep_the_projected_count() {
+    // the shown x4 and x5 are substituted by x3
+    let content = "* show x2 x4 x5\n+1 x3 -1 x4 = 0 ;\n+1 x3 +1 x5 = 1 ;\n+1 x1 +1 x2 +1 x4 >= 1 ;\n+1 x1 +1 x6 >= 1 ;\n";
+    let file = parse_str(content).unwrap();
+    let expected = brute_force(&file);
+    assert_eq!(solve(file, &CountOptions::default()), expected);
+    assert_eq!(expected, BigUint::from(4u32));
+}
+
+#[test]
+fn unsatisfiable_formulas_count_zero_after_preprocessing() {
+    for content in [
+        "+1 x1 >= 1 ;\n+1 ~x1 >= 1 ;\n+1 x2 +1 x3 >= 1 ;\n",
+        "+1 x1 -1 x2 = 0 ;\n+1 x1 +1 x2 = 1 ;\n",
+        "+1 x1 +1 x2 >= 3 ;\n+1 x3 >= 0 ;\n",
+        "+1 x1 >= 1 ;\n+1 ~x1 +1 x2 >= 1 ;\n+1 ~x2 +1 ~x1 >= 1 ;\n",
+    ] {
+        let file = parse_str(content).unwrap();
+        assert_eq!(brute_force(&file), BigUint::from(0u32), "{}", content);
+        let (file, report) = preprocess_with_report(file);
+        assert!(report.unsatisfiable, "{}", content);
+        assert_eq!(count(build_formula(&file), &CountOptions::default()), BigUint::from(0u32), "{}", content);
+    }
+}
+
+#[test]
+fn json_output_reports_eliminated_variables_like_the_text_output() {
+    // x1 is fixed and x3 is substituted by x2
+    let path = std::env::temp_dir().join(format!("preprocessing-{}.opb", std::process::id()));
+    std::fs::write(&path, "+1 x1 >= 1 ;\n+1 x2 -1 x3 = 0 ;\n+1 x2 +1 x4 >= 1 ;\n").unwrap();
+    let count = |format: &str| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_parserTest")).arg("count").arg(&path).args(["--format", format]).output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let (text, json) = (count("text"), count("json"));
+    std::fs::remove_file(&path).unwrap();
+    assert!(text.contains("1 fixed and 1 substituted variables"), "{text}");
+    assert!(json.contains("\"fixed\": 1, \"substituted\": 1"), "{json}");
+}
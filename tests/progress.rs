@@ -0,0 +1,32 @@
+use std::process::Command;
+
+/// Lines of the count command on stderr that report progress.
+fn progress_lines(arguments: &[&str]) -> Vec<String> {
+    let path = std::env::temp_dir().join(format!("progress-{}-{}.opb", std::process::id(), arguments.join("")));
+    std::fs::write(&path, "+1 x1 +1 x2 +1 x3 >= 1 ;\n+1 x3 +1 x4 +1 x5 >= 1 ;\n+1 ~x1 +1 x5 >= 1 ;\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_parserTest")).arg("count").arg(&path).args(arguments).output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().next(), Some("19"));
+    String::from_utf8(output.stderr).unwrap().lines().filter(|l| l.ends_with(" %")).map(str::to_string).collect()
+}
+
+#[test]
+fn progress_is_reported_within_its_range() {
+    for engine in ["plain", "components"] {
+        let lines = progress_lines(&["--engine", engine, "--progress-start", "100", "--progress-end", "200"]);
+        assert!(!lines.is_empty(), "{engine}");
+        assert!(lines.iter().all(|l| (100..200).contains(&l.trim_end_matches(" %").parse::<u32>().unwrap())), "{engine}: {lines:?}");
+    }
+}
+
+#[test]
+fn an_empty_progress_range_writes_nothing() {
+    for engine in ["plain", "components"] {
+        for threads in ["1", "4"] {
+            assert!(progress_lines(&["--engine", engine, "--threads", threads, "--progress-end", "0"]).is_empty(), "{engine}, {threads}");
+        }
+        // the parallel search never reports progress
+        assert!(progress_lines(&["--engine", engine, "--threads", "4"]).is_empty(), "{engine}");
+    }
+}
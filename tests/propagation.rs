@@ -0,0 +1,88 @@
+use parser_test::solving::pb_ds::{PBFormula, TrailFormula};
+use parser_test::solving::weights::LiteralWeights;
+use parser_test::{build_formula, count, count_weighted, parse_str, BigRational, BigUint, CountOptions, Engine};
+
+fn formula(content: &str) -> PBFormula {
+    build_formula(&parse_str(content).unwrap())
+}
+
+fn sorted(mut literals: Vec<(u32, bool)>) -> Vec<(u32, bool)> {
+    literals.sort_unstable();
+    literals
+}
+
+/// x1 implies x2 and x2 implies x3, the variables are numbered in the order they occur.
+const CHAIN: &str = "+1 ~x1 +1 x2 >= 1 ;\n+1 ~x2 +1 x3 >= 1 ;\n+1 x4 +1 x5 >= 1 ;\n";
+
+#[test]
+fn literals_larger_than_the_slack_are_implied() {
+    // the largest left side 5 exceeds the degree by 2, less than the coefficient of x1
+    let (rest, implied) = formula("+3 x1 +1 x2 +1 x3 >= 3 ;\n").propagate();
+    assert_eq!(implied, [(0, true)]);
+    assert!(rest.clauses.is_empty());
+
+    // a negated literal is implied false
+    let (_, implied) = formula("+3 ~x1 +1 x2 +1 x3 >= 3 ;\n").propagate();
+    assert_eq!(implied, [(0, false)]);
+
+    // with x2 unset the slack of 1 forces x1, but not x3 and x4
+    let content = "+3 x1 +1 x2 +1 x3 +1 x4 >= 4 ;\n";
+    let (_, implied) = formula(content).get_sub_formula(1, false);
+    assert_eq!(implied, [(0, true)]);
+    let mut trail = TrailFormula::new(&formula(content));
+    assert_eq!(trail.decide(1, false), Ok(vec![(0, true)]));
+    assert_eq!(trail.unassigned(), [2, 3]);
+}
+
+#[test]
+fn implications_are_followed_to_the_end() {
+    let (rest, implied) = formula(CHAIN).get_sub_formula(0, true);
+    assert_eq!(sorted(implied), [(1, true), (2, true)]);
+    assert_eq!(rest.clauses.len(), 1);
+    let (_, implied) = formula(CHAIN).get_sub_formula(2, false);
+    assert_eq!(sorted(implied), [(0, false), (1, false)]);
+
+    let mut trail = TrailFormula::new(&formula(CHAIN));
+    assert_eq!(trail.decide(0, true), Ok(vec![(1, true), (2, true)]));
+    assert_eq!(trail.decide(3, false), Ok(vec![(4, true)]));
+    assert!(trail.unassigned().is_empty());
+    trail.undo();
+    assert_eq!(trail.unassigned(), [3, 4]);
+    trail.undo();
+    assert_eq!(trail.unassigned(), [0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn conflicts_during_propagation_are_reported_with_their_decisions() {
+    // x1 forces x2 and ~x2
+    let content = "+2 x1 +1 x2 +1 x3 >= 2 ;\n+1 ~x1 +1 x2 >= 1 ;\n+1 ~x1 +1 ~x2 >= 1 ;\n";
+    let (rest, _) = formula(content).get_sub_formula(2, false);
+    assert_eq!(rest.conflict_reason(), Some(&[2][..]));
+    let (rest, _) = formula(content).get_sub_formula(2, true);
+    assert!(!rest.contains_false_clause());
+
+    // x3 forces x6 and ~x6, the conflict is found two implications after the decision on x1
+    let content = format!("{CHAIN}+1 ~x3 +1 x6 >= 1 ;\n+1 ~x3 +1 ~x6 >= 1 ;\n");
+    let (rest, _) = formula(&content).get_sub_formula(0, true);
+    assert!(rest.contains_false_clause());
+    assert_eq!(rest.conflict_reason(), Some(&[0][..]));
+
+    let mut trail = TrailFormula::new(&formula(&content));
+    assert_eq!(trail.decide(3, false), Ok(vec![(4, true)]));
+    assert_eq!(trail.decide(0, true), Err(vec![0]));
+    // the conflict undid x1 and everything it implied, but not the decision on x4
+    assert_eq!(trail.unassigned(), [0, 1, 2, 5]);
+}
+
+#[test]
+fn implied_literals_are_counted_with_their_weight() {
+    // x1 is implied before the first decision, x2 and x3 are free
+    let content = "* weight x1 1/2\n* weight ~x1 5\n+3 x1 +1 x2 +1 x3 >= 3 ;\n";
+    let file = parse_str(content).unwrap();
+    for engine in [Engine::Plain, Engine::Components] {
+        let options = CountOptions { engine, ..CountOptions::default() };
+        assert_eq!(count(build_formula(&file), &options), BigUint::from(4u32), "{engine:?}");
+        let weighted = count_weighted(build_formula(&file), LiteralWeights::from_file(&file), &options);
+        assert_eq!(weighted, BigRational::from_integer(2.into()), "{engine:?}");
+    }
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use num_traits::Zero;
+use common::{models, random_formula};
+use parser_test::solving::ddnnf::Ddnnf;
+use parser_test::solving::weights::{LiteralWeights, Unweighted};
+use parser_test::{build_formula, compile, parse_str, preprocess, BigRational, BigUint, CountOptions};
+
+fn compiled(content: &str) -> Ddnnf {
+    let file = preprocess(parse_str(content).unwrap());
+    compile(build_formula(&file), &file, &CountOptions::default())
+}
+
+/// Models of the formula that agree with the assumptions, as bit sets.
+fn agreeing(content: &str, assumptions: &[(u32, bool)]) -> Vec<u64> {
+    models(&parse_str(content).unwrap()).into_iter()
+        .filter(|m| assumptions.iter().all(|(v, b)| (m >> v & 1 == 1) == *b))
+        .collect()
+}
+
+#[test]
+fn conditioned_counts_match_brute_force() {
+    for seed in 0..10 {
+        let content = random_formula(seed, 10, false);
+        let ddnnf = compiled(&content);
+        for assumptions in [vec![], vec![(0, true)], vec![(1, false), (4, true)], vec![(2, true), (2, false)], vec![(3, false), (5, false), (9, true)]] {
+            let expected = BigUint::from(agreeing(&content, &assumptions).len());
+            assert_eq!(ddnnf.count(&assumptions), expected, "seed {seed}, {assumptions:?}");
+        }
+    }
+}
+
+#[test]
+fn literal_counts_under_assumptions() {
+    // x1 or x2, x3 is declared and free
+    let ddnnf = compiled("* #variable= 3 #constraint= 1\n+1 x1 +1 x2 >= 1 ;\n");
+    let pair = |positive: u32, negative: u32| Some((BigUint::from(positive), BigUint::from(negative)));
+    assert_eq!(ddnnf.literal_counts(&Unweighted, &[]), [pair(4, 2), pair(4, 2), pair(3, 3)]);
+    assert_eq!(ddnnf.literal_counts(&Unweighted, &[(0, true)]), [pair(4, 0), pair(2, 2), pair(2, 2)]);
+}
+
+#[test]
+fn marginals_of_weighted_literals() {
+    // the models ~x1 x2, x1 ~x2 and x1 x2 weigh 3/2, 1/2 and 3/2
+    let content = "* weight x1 1/2\n* weight ~x1 1/2\n* weight x2 3\n+1 x1 +1 x2 >= 1 ;\n";
+    let weights = LiteralWeights::from_file(&parse_str(content).unwrap());
+    let ddnnf = compiled(content);
+    let ratio = |numerator: u32, denominator: u32| Some(BigRational::new(numerator.into(), denominator.into()));
+    assert_eq!(ddnnf.marginals(&weights, &[]), [ratio(4, 7), ratio(6, 7)]);
+    assert_eq!(ddnnf.marginals(&weights, &[(1, false)]), [ratio(1, 1), ratio(0, 1)]);
+    // no model agrees with the assumptions
+    assert_eq!(ddnnf.marginals(&weights, &[(0, false), (1, false)]), [None, None]);
+}
+
+#[test]
+fn substituted_variables_can_be_assumed() {
+    // x4, the variable 1, is substituted by ~x1
+    let content = "+1 x1 +1 x4 = 1 ;\n+1 x1 +1 x2 +1 x3 >= 2 ;\n";
+    let ddnnf = compiled(content);
+    assert_eq!(ddnnf.count(&[(1, true)]), BigUint::from(agreeing(content, &[(1, true)]).len()));
+    assert_eq!(ddnnf.count(&[(1, true), (0, true)]), BigUint::zero());
+    let counts = ddnnf.literal_counts(&Unweighted, &[]);
+    assert_eq!(counts[1], Some((BigUint::from(1u32), BigUint::from(3u32))));
+}
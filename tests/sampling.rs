@@ -0,0 +1,79 @@
+mod common;
+
+use std::collections::{HashMap, HashSet};
+use common::{brute_force, models, random_formula};
+use parser_test::solving::sampling::Sampler;
+use parser_test::{build_formula, compile, parse_str, preprocess, BigUint, CountOptions, Engine};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Draws `k` samples of the file like `sample`.
+fn sample(content: &str, options: &CountOptions, k: usize) -> Vec<Vec<(u32, bool)>> {
+    let mut file = parse_str(content).unwrap();
+    file.name_declared_variables();
+    let variables = file.variable_count();
+    let file = preprocess(file);
+    let ddnnf = compile(build_formula(&file), &file, options);
+    let mut sampler = Sampler::new(&ddnnf, &[]).unwrap();
+    sampler.variables = variables;
+    sampler.samples(&mut StdRng::seed_from_u64(0), k)
+}
+
+fn bits(model: &[(u32, bool)]) -> u64 {
+    model.iter().filter(|(_, b)| *b).map(|(v, _)| 1 << v).sum()
+}
+
+#[test]
+fn samples_are_models_of_the_original_variables() {
+    for seed in 0..10 {
+        let content = random_formula(seed, 10, false);
+        let file = parse_str(&content).unwrap();
+        let models = models(&file);
+        if models.is_empty() {
+            continue;
+        }
+        for engine in [Engine::Plain, Engine::Components] {
+            let options = CountOptions { engine, ..CountOptions::default() };
+            for model in sample(&content, &options, 50) {
+                assert_eq!(model.iter().map(|(v, _)| *v).collect::<Vec<_>>(), (0..10).collect::<Vec<_>>(), "seed {seed}, {engine:?}");
+                assert!(models.contains(&bits(&model)), "seed {seed}, {engine:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn samples_contain_declared_but_not_auxiliary_variables() {
+    // x5 and x6 are declared but unused, x1 x2 gets an auxiliary variable and x4 is substituted by x1
+    let content = "* #variable= 6 #constraint= 2\n+1 x1 x2 +1 x3 >= 1 ;\n+1 x1 -1 x4 = 0 ;\n";
+    let models = models(&parse_str(content).unwrap());
+    assert_eq!(models.len(), 20);
+    let samples = sample(content, &CountOptions::default(), 4000);
+    let mut frequency: HashMap<u64, usize> = HashMap::new();
+    for model in &samples {
+        assert_eq!(model.iter().map(|(v, _)| *v).collect::<Vec<_>>(), (0..6).collect::<Vec<_>>());
+        *frequency.entry(bits(model)).or_default() += 1;
+    }
+    // every model is expected 200 times
+    assert_eq!(frequency.len(), 20);
+    assert!(frequency.iter().all(|(m, f)| models.contains(m) && (120..280).contains(f)), "{frequency:?}");
+}
+
+#[test]
+fn projected_samples_contain_the_shown_variables() {
+    for seed in 0..10 {
+        let content = format!("{}* show x1 x2 x3 x5 x8\n", random_formula(seed, 10, false));
+        let file = parse_str(&content).unwrap();
+        if models(&file).is_empty() {
+            continue;
+        }
+        let options = CountOptions { projection: file.projection.clone(), ..CountOptions::default() };
+        let mut shown = file.projection.clone().unwrap();
+        shown.sort_unstable();
+        // at most 32 projected models, 500 samples draw all of them
+        let samples = sample(&content, &options, 500);
+        assert!(samples.iter().all(|m| m.iter().map(|(v, _)| *v).eq(shown.iter().copied())), "seed {seed}");
+        let distinct: HashSet<u64> = samples.iter().map(|m| bits(m)).collect();
+        assert_eq!(BigUint::from(distinct.len()), brute_force(&file), "seed {seed}");
+    }
+}
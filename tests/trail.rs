@@ -0,0 +1,130 @@
+mod common;
+
+use std::collections::HashMap;
+use common::random_formula;
+use parser_test::solving::pb_ds::{PBFormula, TrailFormula};
+use parser_test::{build_formula, parse_str};
+
+fn formula(content: &str) -> PBFormula {
+    build_formula(&parse_str(content).unwrap())
+}
+
+/// Cache key of the whole formula under the assignment of the trail.
+fn key(formula: &TrailFormula) -> Box<[i64]> {
+    let (variables, constraints) = (formula.unassigned(), formula.active_constraints());
+    formula.residual(&variables, &constraints).cache_key()
+}
+
+/// Copy of the whole formula under the assignment of the trail.
+fn copy(formula: &TrailFormula) -> PBFormula {
+    let (variables, constraints) = (formula.unassigned(), formula.active_constraints());
+    formula.residual(&variables, &constraints).to_formula()
+}
+
+#[test]
+fn residuals_match_the_copied_formula() {
+    for seed in 0..20 {
+        let mut copied = formula(&random_formula(seed, 12, false));
+        let mut trail = TrailFormula::new(&copied);
+        assert_eq!(copy(&trail).cache_key(), copied.cache_key(), "seed {seed}");
+        // before the first conflict nothing is learned, so both propagate the same literals
+        for v in 0..12 {
+            if !trail.unassigned().contains(&v) {
+                continue;
+            }
+            let value = (seed + v as u64).is_multiple_of(2);
+            let (next, _) = copied.get_sub_formula(v, value);
+            if trail.decide(v, value).is_err() {
+                assert!(next.contains_false_clause(), "seed {seed}, x{v}");
+                break;
+            }
+            copied = next;
+            assert_eq!(trail.unassigned(), copied.variables, "seed {seed}, x{v}");
+            assert_eq!(copy(&trail).cache_key(), copied.cache_key(), "seed {seed}, x{v}");
+        }
+    }
+}
+
+#[test]
+fn equal_keys_belong_to_equal_subformulas() {
+    let mut formulas: HashMap<Box<[i64]>, Box<[i64]>> = HashMap::new();
+    for seed in 0..10 {
+        let mut trail = TrailFormula::new(&formula(&random_formula(seed, 10, false)));
+        let variables = trail.unassigned();
+        // the components below every pair of decisions, many of them recur
+        for (u, v) in (0..10).flat_map(|u| (0..10).map(move |v| (u, v))).filter(|(u, v)| u != v) {
+            let decided = [(u, true), (v, false)].into_iter().filter(|(x, value)| trail.unassigned().contains(x) && trail.decide(*x, *value).is_ok()).count();
+            let (components, _) = trail.components(&variables);
+            for component in components {
+                let residual = trail.residual(&component.variables, &component.constraints);
+                let expected = residual.to_formula().cache_key();
+                assert_eq!(formulas.entry(residual.cache_key()).or_insert_with(|| expected.clone()), &expected, "seed {seed}");
+            }
+            for _ in 0..decided {
+                trail.undo();
+            }
+        }
+    }
+}
+
+#[test]
+fn undo_restores_the_counters() {
+    for seed in 0..20 {
+        let mut trail = TrailFormula::new(&formula(&random_formula(seed, 12, false)));
+        let (variables, initial) = (trail.unassigned(), key(&trail));
+        let mut decisions = 0;
+        for v in 0..12 {
+            if trail.unassigned().contains(&v) && trail.decide(v, seed.is_multiple_of(2)).is_ok() {
+                decisions += 1;
+            }
+        }
+        for _ in 0..decisions {
+            trail.undo();
+        }
+        assert_eq!(trail.unassigned(), variables, "seed {seed}");
+        assert_eq!(key(&trail), initial, "seed {seed}");
+    }
+}
+
+#[test]
+fn residuals_only_contain_unassigned_literals() {
+    let mut trail = TrailFormula::new(&formula("+2 x1 +1 x2 +1 x3 >= 2 ;\n+1 x3 +1 x4 >= 1 ;\n"));
+    assert_eq!(trail.decide(1, true), Ok(vec![]));
+    let (variables, constraints) = (trail.unassigned(), trail.active_constraints());
+    let residual = trail.residual(&variables, &constraints);
+    assert_eq!(residual.variables, [0, 2, 3]);
+    assert_eq!(residual.constraints, [0, 1]);
+    assert_eq!(residual.degree(0), 1);
+    assert_eq!(residual.literals(0).map(|l| (l.name, l.factor)).collect::<Vec<_>>(), [(0, 2), (2, 1)]);
+    assert_eq!(residual.occurrences(2).collect::<Vec<_>>(), [(0, 1), (1, 1)]);
+    assert!(!residual.is_empty());
+
+    // setting x3 satisfies both constraints, which removes them from the occurrences of x1 and x4
+    assert_eq!(trail.decide(2, true), Ok(vec![]));
+    assert_eq!(trail.active_constraints(), Vec::<usize>::new());
+    let residual = trail.residual(&variables, &constraints);
+    assert_eq!(residual.occurrences(0).count(), 0);
+    assert_eq!(residual.occurrences(3).count(), 0);
+}
+
+#[test]
+fn components_split_on_the_unassigned_variables() {
+    let mut trail = TrailFormula::new(&formula("+1 x1 +1 x2 >= 1 ;\n+1 x2 +1 x3 >= 1 ;\n+1 x4 +1 x5 >= 1 ;\n"));
+    let variables = trail.unassigned();
+    let (components, free) = trail.components(&variables);
+    let split: Vec<(Vec<u32>, Vec<usize>)> = components.into_iter().map(|c| (c.variables, c.constraints)).collect();
+    assert_eq!(split, [(vec![0, 1, 2], vec![0, 1]), (vec![3, 4], vec![2])]);
+    assert!(free.is_empty());
+
+    // x2 satisfies the first two constraints, x1 and x3 become free
+    assert_eq!(trail.decide(1, true), Ok(vec![]));
+    let (components, free) = trail.components(&variables);
+    assert_eq!(components.len(), 1);
+    assert_eq!(components[0].variables, [3, 4]);
+    assert_eq!(free, [0, 2]);
+
+    trail.undo();
+    let (components, free) = trail.components(&variables);
+    assert_eq!(components.len(), 2);
+    assert!(free.is_empty());
+}
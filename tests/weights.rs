@@ -0,0 +1,38 @@
+mod common;
+
+use common::solve_weighted;
+use parser_test::{parse_str, BigRational, CountOptions, Engine};
+
+fn ratio(numerator: i64, denominator: i64) -> BigRational {
+    BigRational::new(numerator.into(), denominator.into())
+}
+
+#[test]
+fn weights_are_parsed_exactly() {
+    let file = parse_str("* weight x1 0.25\n* weight ~x1 1/3\n* weight x2 2e-1\n* weight ~x2 3\n+1 x1 +1 x2 >= 1 ;\n").unwrap();
+    let weights: Vec<(u32, bool, BigRational)> = file.weights.iter().map(|w| (w.name, w.negated, w.weight.clone())).collect();
+    assert_eq!(weights, [(0, false, ratio(1, 4)), (0, true, ratio(1, 3)), (1, false, ratio(1, 5)), (1, true, ratio(3, 1))]);
+}
+
+#[test]
+fn invalid_weights_are_rejected() {
+    let error = parse_str("+1 x1 >= 1 ;\n* weight x1 1/0\n").err().unwrap();
+    assert_eq!(error.line, 2);
+    assert!(error.message.contains("1/0"), "{}", error.message);
+}
+
+#[test]
+fn literals_without_a_weight_weigh_one() {
+    // the models x1 ~x2, ~x1 x2 and x1 x2 weigh 1/2, 1/2 and 1/2, the free x3 multiplies by 1/4 + 1
+    let content = "* #variable= 3 #constraint= 1\n* weight x1 1/2\n* weight ~x1 1/2\n* weight x3 1/4\n+1 x1 +1 x2 >= 1 ;\n";
+    for engine in [Engine::Plain, Engine::Components] {
+        let options = CountOptions { engine, ..CountOptions::default() };
+        assert_eq!(solve_weighted(parse_str(content).unwrap(), &options), ratio(15, 8), "{engine:?}");
+    }
+}
+
+#[test]
+fn zero_weights_remove_models() {
+    let content = "* weight x1 0\n* weight ~x2 0\n+1 x1 +1 x2 >= 1 ;\n";
+    assert_eq!(solve_weighted(parse_str(content).unwrap(), &CountOptions::default()), ratio(1, 1));
+}